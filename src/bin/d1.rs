@@ -1,32 +1,42 @@
 use std::{
+    collections::HashMap,
     fs::File,
     io::{BufRead, BufReader},
     iter::zip,
     path::{Path, PathBuf},
 };
 
-fn parse_input<P: AsRef<Path>>(file: P) -> anyhow::Result<Vec<(i32, i32)>> {
+use aoc::Part;
+use clap::Parser;
+
+// Parse the two columns one line at a time.  Lines that aren't exactly two
+// numbers are skipped unless `strict` is set, in which case they are an error.
+fn parse_input<P: AsRef<Path>>(file: P, strict: bool) -> anyhow::Result<Vec<(i32, i32)>> {
     let f = File::open(PathBuf::from(".").join("inputs").join(file.as_ref()))?;
-    let buf = BufReader::new(f);
-    let pairs = buf
-        .lines()
-        .into_iter()
-        .filter_map(|l| l.ok())
-        .map(|l| {
-            l.split_whitespace()
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>()
-        })
-        .filter_map(|parts| {
-            let mut it = parts.into_iter();
-            Some((it.next()?, it.next()?))
-        })
-        .filter_map(|(a, b)| {
-            let a: i32 = a.parse().ok()?;
-            let b: i32 = b.parse().ok()?;
-            Some((a, b))
-        })
-        .collect::<Vec<(i32, i32)>>();
+    parse_pairs(BufReader::new(f), strict)
+}
+
+fn parse_pairs<R: BufRead>(reader: R, strict: bool) -> anyhow::Result<Vec<(i32, i32)>> {
+    let mut pairs = Vec::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut it = line.split_whitespace();
+        let pair = match (it.next(), it.next(), it.next()) {
+            (Some(a), Some(b), None) => a.parse::<i32>().ok().zip(b.parse::<i32>().ok()),
+            _ => None,
+        };
+        match pair {
+            Some(pair) => pairs.push(pair),
+            None if strict => {
+                anyhow::bail!("Malformed line {}: {line:?}", idx + 1);
+            }
+            None => continue,
+        }
+    }
     Ok(pairs)
 }
 
@@ -36,38 +46,74 @@ fn pairs_to_cols(pairs: Vec<(i32, i32)>) -> (Vec<i32>, Vec<i32>) {
     (left, right)
 }
 
-fn part1() -> anyhow::Result<()> {
-    let (mut left, mut right) = pairs_to_cols(parse_input("d1-p1.txt")?);
-    left.sort();
-    right.sort();
+fn total_distance(left: &[i32], right: &[i32]) -> i64 {
+    let mut left = left.to_vec();
+    let mut right = right.to_vec();
+    left.sort_unstable();
+    right.sort_unstable();
 
-    let total_distance: i32 = zip(left, right).map(|(a, b)| (a - b).abs()).sum();
+    zip(left, right)
+        .map(|(a, b)| (a as i64 - b as i64).abs())
+        .sum()
+}
 
-    println!("Total Distance: {total_distance}");
-    Ok(())
+fn similarity_score(left: &[i32], right: &[i32]) -> i64 {
+    // count occurrences in the right column up front rather than rescanning
+    // it for every entry on the left.
+    let mut counts: HashMap<i32, i64> = HashMap::new();
+    for b in right {
+        *counts.entry(*b).or_default() += 1;
+    }
+
+    left.iter()
+        .map(|a| *a as i64 * counts.get(a).copied().unwrap_or(0))
+        .sum()
 }
 
-fn part2() -> anyhow::Result<()> {
-    let (left, right) = pairs_to_cols(parse_input("d1-p2.txt")?);
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d1-p1.txt")]
+    input: String,
 
-    // we'll just do this naive in quadratic time
-    let mut similarity_score = 0;
-    for a in left.iter() {
-        let mut dups = 0;
-        for b in right.iter() {
-            if *a == *b {
-                dups += 1;
-            }
-        }
-        similarity_score += dups * a;
-    }
+    #[arg(short, long, value_enum, default_value_t)]
+    part: Part,
 
-    println!("Similary Score: {similarity_score}");
-    Ok(())
+    /// Fail on lines that aren't exactly two numbers rather than skipping them
+    #[arg(long)]
+    strict: bool,
 }
 
 fn main() -> anyhow::Result<()> {
-    part1()?;
-    part2()?;
+    let cli = Cli::parse();
+    let (left, right) = pairs_to_cols(parse_input(&cli.input, cli.strict)?);
+    if cli.part.part1() {
+        println!("Part 1: {}", total_distance(&left, &right));
+    }
+    if cli.part.part2() {
+        println!("Part 2: {}", similarity_score(&left, &right));
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example() {
+        let (left, right) = pairs_to_cols(parse_input("d1-example.txt", true).unwrap());
+        assert_eq!(total_distance(&left, &right), 11);
+        assert_eq!(similarity_score(&left, &right), 31);
+    }
+
+    #[test]
+    fn strict_rejects_a_single_token_line() {
+        let input = "3   4\n4\n2   5\n";
+        let err = parse_pairs(input.as_bytes(), true).unwrap_err();
+        assert_eq!(err.to_string(), "Malformed line 2: \"4\"");
+
+        // the lenient default skips it and keeps the rest
+        let pairs = parse_pairs(input.as_bytes(), false).unwrap();
+        assert_eq!(pairs, [(3, 4), (2, 5)]);
+    }
+}
@@ -1,23 +1,45 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::File,
     io::{BufRead, BufReader},
     path::{Path, PathBuf},
     usize,
 };
 
+use clap::Parser;
+
 type TopoMap = Vec<Vec<u8>>;
 
+// Height for cells without one (e.g. '.'); never part of a trail
+const IMPASSABLE: u8 = u8::MAX;
+
+// Read the map from the named file in inputs/, or from stdin for "-"
 fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<TopoMap> {
-    let full_path = PathBuf::from(".").join("inputs").join(path);
-    let f = File::open(full_path)?;
-    let reader = BufReader::new(f);
-    let topo: TopoMap = reader
+    let reader: Box<dyn BufRead> = if path.as_ref() == Path::new("-") {
+        Box::new(std::io::stdin().lock())
+    } else {
+        let full_path = PathBuf::from(".").join("inputs").join(path);
+        Box::new(BufReader::new(File::open(full_path)?))
+    };
+    Ok(parse_topo(reader))
+}
+
+fn parse_topo<R: BufRead>(reader: R) -> TopoMap {
+    reader
         .lines()
         .filter_map(|l| l.ok())
-        .map(|l| l.bytes().map(|b| b - b'0').collect())
-        .collect();
-    Ok(topo)
+        .map(|l| {
+            l.bytes()
+                .map(|b| {
+                    if b.is_ascii_digit() {
+                        b - b'0'
+                    } else {
+                        IMPASSABLE
+                    }
+                })
+                .collect()
+        })
+        .collect()
 }
 
 fn trailheads_for_map(map: &TopoMap) -> Vec<(usize, usize)> {
@@ -44,33 +66,35 @@ fn valid_position(
         return None;
     };
 
-    if row < map.len() && col < map[0].len() {
+    if row < map.len() && col < map[0].len() && map[row][col] != IMPASSABLE {
         Some((row, col))
     } else {
         None
     }
 }
 
-fn find_walkable_trails(
+// Summits reachable from a cell and the number of distinct trails that get
+// there; shared between every trail passing through that cell.
+type CellAnalysis = (HashSet<(usize, usize)>, usize);
+
+fn analyze_trails(
     map: &TopoMap,
     level: u8,
     position: (usize, usize),
-) -> HashSet<(usize, usize)> {
+    memo: &mut HashMap<(usize, usize), CellAnalysis>,
+) -> CellAnalysis {
     let elevation = map[position.0][position.1];
-    let mut res = HashSet::new();
 
     if level != elevation {
-        return res;
+        return (HashSet::new(), 0);
     }
 
-    // for _ in 0..level {
-    //     print!(" ");
-    // }
-    // println!("{}:{} => {level}", position.0, position.1);
-
     if level == 9 {
-        res.insert(position);
-        return res;
+        return (HashSet::from([position]), 1);
+    }
+
+    if let Some(analysis) = memo.get(&position) {
+        return analysis.clone();
     }
 
     let left = (position.0.into(), position.1.checked_add_signed(-1));
@@ -79,77 +103,112 @@ fn find_walkable_trails(
     let down = (position.0.checked_add_signed(1), position.1.into());
 
     let positions = [left, right, up, down];
+    let mut summits = HashSet::new();
+    let mut trails = 0;
     for pos in positions {
         if let Some(pos) = valid_position(map, pos) {
-            // update our result witht the set union of positions
-            res.extend(&find_walkable_trails(map, level + 1, pos));
+            // summits are the set union, trails are the sum over each step
+            let (next_summits, next_trails) = analyze_trails(map, level + 1, pos, memo);
+            summits.extend(next_summits);
+            trails += next_trails;
         }
     }
 
-    res
+    memo.insert(position, (summits.clone(), trails));
+    (summits, trails)
 }
 
-fn score_trails(map: &TopoMap, level: u8, position: (usize, usize)) -> usize {
-    let elevation = map[position.0][position.1];
-
-    if level != elevation {
-        return 0;
-    }
+/// Walk the trails from a trailhead once, returning its (score, rating): the
+/// number of distinct summits reachable and the number of distinct trails.
+fn analyze_trailhead(map: &TopoMap, trailhead: (usize, usize)) -> (usize, usize) {
+    let (summits, trails) = analyze_trails(map, 0, trailhead, &mut HashMap::new());
+    (summits.len(), trails)
+}
 
-    // for _ in 0..level {
-    //     print!(" ");
-    // }
-    // println!("{}:{} => {level}", position.0, position.1);
+// The sum of the scores (part 1) and the sum of the ratings (part 2) of
+// every trailhead on the map.
+fn solve(map: &TopoMap) -> (usize, usize) {
+    trailheads_for_map(map)
+        .into_iter()
+        .map(|th| analyze_trailhead(map, th))
+        .fold((0, 0), |(scores, ratings), (score, rating)| {
+            (scores + score, ratings + rating)
+        })
+}
 
-    if level == 9 {
-        return 1;
-    }
+#[derive(Debug, Parser)]
+struct Cli {
+    /// Input file in inputs/, or - to read the map from stdin
+    #[arg(short, long, default_value = "d10.txt")]
+    input: String,
 
-    let left = (position.0.into(), position.1.checked_add_signed(-1));
-    let right = (position.0.into(), position.1.checked_add_signed(1));
-    let up = (position.0.checked_add_signed(-1), position.1.into());
-    let down = (position.0.checked_add_signed(1), position.1.into());
+    /// Print the score and rating of each trailhead
+    #[arg(short, long)]
+    verbose: bool,
+}
 
-    let positions = [left, right, up, down];
-    let mut res = 0;
-    for pos in positions {
-        if let Some(pos) = valid_position(map, pos) {
-            // update our result witht the set union of positions
-            res += score_trails(map, level + 1, pos);
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let map = parse_input(&cli.input)?;
+    if cli.verbose {
+        let trailheads = trailheads_for_map(&map);
+        println!("There are {} trailheads", trailheads.len());
+        for trailhead in trailheads {
+            let (score, rating) = analyze_trailhead(&map, trailhead);
+            println!("{trailhead:?} => score {score}, rating {rating}");
         }
     }
 
-    res
-}
+    let (score, rating) = solve(&map);
+    println!("Part 1: {score}");
+    println!("Part 2: {rating}");
 
-fn score_trailhead(map: &TopoMap, trailhead: (usize, usize)) -> usize {
-    find_walkable_trails(map, 0, trailhead).len()
-}
-
-fn rate_trailhead(map: &TopoMap, trailhead: (usize, usize)) -> usize {
-    score_trails(map, 0, trailhead)
+    Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
-    let map = parse_input("d10.txt")?;
-    let trailheads = trailheads_for_map(&map);
-    println!("There are {} trailheads", trailheads.len());
-
-    // By Score (Part 1)
-    for trailhead in trailheads.iter() {
-        let score = score_trailhead(&map, *trailhead);
-        println!("{trailhead:?} => {score}")
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732";
+    const EXAMPLE_ANSWERS: (usize, usize) = (36, 81);
+
+    // The puzzle's smaller maps, with '.' for cells that aren't part of any
+    // trail, and the (score, rating) summed over their trailheads.
+    const DOTTED_EXAMPLES: [(&str, (usize, usize)); 3] = [
+        (
+            "..90..9\n...1.98\n...2..7\n6543456\n765.987\n876....\n987....",
+            (4, 13),
+        ),
+        (
+            "10..9..\n2...8..\n3...7..\n4567654\n...8..3\n...9..2\n.....01",
+            (3, 3),
+        ),
+        (
+            ".....0.\n..4321.\n..5..2.\n..6543.\n..7..4.\n..8765.\n..9....",
+            (1, 3),
+        ),
+    ];
+
+    #[test]
+    fn example() {
+        assert_eq!(solve(&parse_topo(EXAMPLE.as_bytes())), EXAMPLE_ANSWERS);
     }
-    let sum: usize = trailheads.iter().map(|th| score_trailhead(&map, *th)).sum();
-    println!("Total Score: {sum}");
 
-    // By Rating (Part 2)
-    for trailhead in trailheads.iter() {
-        let rating = rate_trailhead(&map, *trailhead);
-        println!("{trailhead:?} => {rating}")
+    #[test]
+    fn dotted_examples() {
+        for (map, answers) in DOTTED_EXAMPLES {
+            let map = parse_topo(map.as_bytes());
+            assert!(map.iter().flatten().any(|&h| h == IMPASSABLE));
+            assert_eq!(solve(&map), answers, "{map:?}");
+        }
     }
-    let sum: usize = trailheads.iter().map(|th| rate_trailhead(&map, *th)).sum();
-    println!("Total Rating: {sum}");
-
-    Ok(())
 }
@@ -1,17 +1,34 @@
 use std::{
+    collections::HashMap,
     fs::File,
+    io::Read,
     path::{Path, PathBuf},
 };
 
+use clap::Parser;
+
 // with the replacement going on, at first blush I'm getting the feeling that
 // we want some kind of balanced binary tree sort of thing...  That may not
 // be right, however, so let's send it naive first.
+//
+// The stones are read from the named file in inputs/, or from stdin for "-".
 fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<usize>> {
-    let full_path = PathBuf::from(".").join("inputs").join(path);
-    let s = std::io::read_to_string(File::open(full_path)?)?;
-    Ok(s.split_whitespace()
-        .map(|stone| stone.parse::<usize>().unwrap())
-        .collect::<Vec<usize>>())
+    let path = path.as_ref();
+    let s = if path == Path::new("-") {
+        let mut s = String::new();
+        std::io::stdin().read_to_string(&mut s)?;
+        s
+    } else {
+        let full_path = PathBuf::from(".").join("inputs").join(path);
+        std::io::read_to_string(File::open(full_path)?)?
+    };
+    s.split_whitespace()
+        .map(|stone| {
+            stone
+                .parse::<usize>()
+                .map_err(|e| anyhow::anyhow!("Bad stone {stone:?}: {e}"))
+        })
+        .collect()
 }
 
 // Rules:
@@ -25,65 +42,152 @@ fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<usize>> {
 //    would become stones 10 and 0.)
 // 3. If none of the other rules apply, the stone is replaced by a new stone;
 //    the old stone's number multiplied by 2024 is engraved on the new stone.
-#[memoize::memoize]
-fn count(stone: usize, generation: usize) -> usize {
-    if generation == 0 {
-        return 1;
-    }
+//
+// Split the `digits` digits of `n` into its left and right halves, e.g.
+// 123456 -> (123, 456); used by blink, so no allocating.
+fn split_digits_arithmetic(n: usize, digits: u32) -> (usize, usize) {
+    let half = 10usize.pow(digits / 2);
+    (n / half, n % half)
+}
 
+// Returns the stone(s) that replace `stone` after a single blink.
+fn blink(stone: usize) -> (usize, Option<usize>) {
     // Rule 1
     if stone == 0 {
-        return count(1, generation - 1);
+        return (1, None);
     }
 
     // Rule 2
     // the log base 10 of a number is the number of digits
     let digits = stone.ilog10() + 1;
     if digits % 2 == 0 {
-        // left digits
-        let mut num = stone;
-        let mut left = 0;
-        let mut right = 0;
-        for dig_idx in 0..digits {
-            let digit = num % 10;
-            let mid_idx = digits / 2;
-            let pow = if dig_idx < mid_idx {
-                dig_idx
-            } else {
-                // ex: for a 4-digit number we want to raise digit
-                // with index 3 (the last digit) by 1.  The mid_idx
-                // copmuted is 2 (4 / 2), so we want dig_idx - mid_idx
-                dig_idx - mid_idx
-            };
-            num /= 10;
-            if dig_idx < mid_idx {
-                right += digit * 10usize.pow(pow);
-            } else {
-                left += digit * 10usize.pow(pow);
-            }
-        }
-
-        // recurse on our left and right digits
-        return count(left, generation - 1) + count(right, generation - 1);
+        let (left, right) = split_digits_arithmetic(stone, digits);
+        return (left, Some(right));
     }
 
     // Rule 3
-    count(stone * 2024, generation - 1)
+    (stone * 2024, None)
+}
+
+/// Number of stones `stone` turns into after `generation` blinks, memoizing
+/// into `cache` so the caller decides how long results are kept around.
+fn count_with_cache(
+    cache: &mut HashMap<(usize, usize), usize>,
+    stone: usize,
+    generation: usize,
+) -> usize {
+    if generation == 0 {
+        return 1;
+    }
+    if let Some(&known) = cache.get(&(stone, generation)) {
+        return known;
+    }
+
+    let result = match blink(stone) {
+        (left, Some(right)) => {
+            count_with_cache(cache, left, generation - 1)
+                + count_with_cache(cache, right, generation - 1)
+        }
+        (stone, None) => count_with_cache(cache, stone, generation - 1),
+    };
+    cache.insert((stone, generation), result);
+    result
+}
+
+// Total number of stones after `blinks` blinks, with a cache that only
+// lives as long as this call.
+fn solve(stones: &[usize], blinks: usize) -> usize {
+    let mut cache = HashMap::new();
+    stones
+        .iter()
+        .map(|stone| count_with_cache(&mut cache, *stone, blinks))
+        .sum()
+}
+
+#[derive(Debug, Parser)]
+struct Cli {
+    /// Input file in inputs/, or - to read the stones from stdin
+    #[arg(short, long, default_value = "d11.txt")]
+    input: String,
 }
 
 fn main() -> anyhow::Result<()> {
-    let stones = parse_input("d11.txt")?;
+    let cli = Cli::parse();
+    let stones = parse_input(&cli.input)?;
     println!("Stones: {stones:?}");
 
-    // Blink 25 times
-    println!("Part 1:");
-    let count_25: usize = stones.iter().map(|stone| count(*stone, 25)).sum();
-    println!("Blink 25: Count = {count_25}");
-
-    // Now blink another 50 times...
-    println!("\n\nPart 2:");
-    let count_75: usize = stones.iter().map(|stone| count(*stone, 75)).sum();
-    println!("Blink 75: Count = {count_75}");
+    // Blink 25 times, then another 50 times...
+    println!("Part 1: {} (25 blinks)", solve(&stones, 25));
+    println!("Part 2: {} (75 blinks)", solve(&stones, 75));
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Same as `split_digits_arithmetic` by way of the decimal string, as an
+    // easy to trust version to check it against.
+    fn split_digits(n: usize) -> (usize, usize) {
+        let s = n.to_string();
+        let (left, right) = s.split_at(s.len() / 2);
+        (left.parse().unwrap(), right.parse().unwrap())
+    }
+
+    // Same as `count_with_cache` but with the results memoized for the life
+    // of the process, to check the two against each other.
+    #[memoize::memoize]
+    fn count(stone: usize, generation: usize) -> usize {
+        if generation == 0 {
+            return 1;
+        }
+
+        // recurse on the replacement stone(s)
+        match blink(stone) {
+            (left, Some(right)) => count(left, generation - 1) + count(right, generation - 1),
+            (stone, None) => count(stone, generation - 1),
+        }
+    }
+
+    const EXAMPLE_STONES: [usize; 2] = [125, 17];
+    const EXAMPLE_BLINKS: usize = 25;
+    const EXAMPLE_COUNT: usize = 55312;
+
+    // (number, halves) for a few even digit counts
+    const SPLIT_CASES: [(usize, (usize, usize)); 4] = [
+        (10, (1, 0)),
+        (1000, (10, 0)),
+        (9999, (99, 99)),
+        (123456, (123, 456)),
+    ];
+
+    #[test]
+    fn split_digits_cases() {
+        for (n, expected) in SPLIT_CASES {
+            assert_eq!(split_digits(n), expected, "{n}");
+        }
+    }
+
+    #[test]
+    fn split_digits_arithmetic_matches_string() {
+        let even_digits = (1..1_000_000usize).filter(|n| (n.ilog10() + 1) % 2 == 0);
+        for n in SPLIT_CASES.iter().map(|(n, _)| *n).chain(even_digits) {
+            assert_eq!(
+                split_digits_arithmetic(n, n.ilog10() + 1),
+                split_digits(n),
+                "{n}"
+            );
+        }
+    }
+
+    #[test]
+    fn example() {
+        let memoized: usize = EXAMPLE_STONES
+            .iter()
+            .map(|stone| count(*stone, EXAMPLE_BLINKS))
+            .sum();
+        assert_eq!(memoized, EXAMPLE_COUNT);
+        assert_eq!(solve(&EXAMPLE_STONES, EXAMPLE_BLINKS), EXAMPLE_COUNT);
+    }
+}
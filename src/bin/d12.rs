@@ -5,6 +5,10 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use anyhow::Context;
+use aoc::{direction::Direction, Part};
+use clap::{Parser, ValueEnum};
+
 fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Vec<char>>> {
     let full_path = PathBuf::from(".").join("inputs").join(path);
     let f = File::open(full_path)?;
@@ -19,13 +23,37 @@ fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Vec<char>>> {
 
 #[derive(Debug, Clone)]
 struct CropArea {
-    _crop: char,
+    crop: char,
     members: HashSet<(usize, usize)>,
     row_count: usize,
     col_count: usize,
+    connectivity: Connectivity,
 }
 
 const NEIGHBOR_OFFSETS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const DIAGONAL_OFFSETS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+/// Which neighboring plots join a region; the puzzle only uses `Four`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Connectivity {
+    /// Plots sharing an edge
+    #[default]
+    Four,
+    /// Plots sharing an edge or a corner
+    Eight,
+}
+
+impl Connectivity {
+    fn offsets(&self) -> impl Iterator<Item = (isize, isize)> {
+        let diagonals: &'static [(isize, isize)] = match self {
+            Self::Four => &[],
+            Self::Eight => &DIAGONAL_OFFSETS,
+        };
+        NEIGHBOR_OFFSETS
+            .into_iter()
+            .chain(diagonals.iter().copied())
+    }
+}
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 struct Side {
@@ -54,32 +82,50 @@ impl CropArea {
         return self.members.len();
     }
 
-    fn perimeter(&self) -> usize {
+    // Every outward facing cell edge of the region as (row, col, side),
+    // sorted so that the edges of each cell are together.
+    fn perimeter_edges(&self) -> Vec<(usize, usize, Direction)> {
+        let mut edges = Vec::new();
+        for (x, y) in self.members.iter().cloned() {
+            for side in Direction::ALL {
+                // rows run down the map, so a side's row offset is its dy
+                let (col_off, row_off) = side.dx_dy();
+                if self.has_perimeter_at_offset(x, y, row_off, col_off) {
+                    edges.push((x, y, side));
+                }
+            }
+        }
+        edges.sort();
+        edges
+    }
+
+    // None for 8-connected regions, whose outline isn't made of cell edges
+    fn perimeter(&self) -> Option<usize> {
+        if self.connectivity != Connectivity::Four {
+            return None;
+        }
+
         // start naive; look at each and see if it has neighbors in each of the
         // four directions.  This is quadratic and might be a problem for
         // part 2 (I am assuming).  For now, let's do it dumb and see how that
         // plays out...
-        let mut perimeter = 0;
-        for (x, y) in self.members.iter().cloned() {
-            let count = NEIGHBOR_OFFSETS
-                .iter()
-                .filter(|(x_off, y_off)| self.has_perimeter_at_offset(x, y, *x_off, *y_off))
-                .count();
-            perimeter += count;
-        }
-
-        perimeter
+        Some(self.perimeter_edges().len())
     }
 
-    fn price(&self) -> usize {
-        self.area() * self.perimeter()
+    fn price(&self) -> Option<usize> {
+        Some(self.area() * self.perimeter()?)
     }
 
-    fn bulk_price(&self) -> usize {
-        self.sides() * self.area()
+    fn bulk_price(&self) -> Option<usize> {
+        Some(self.sides()? * self.area())
     }
 
-    fn sides(&self) -> usize {
+    // None for 8-connected regions, like `perimeter`
+    fn sides(&self) -> Option<usize> {
+        if self.connectivity != Connectivity::Four {
+            return None;
+        }
+
         // for the bulk price, we multiple the area by the number of "sides" that
         // are continguous.  To count this algorithmically we'll consider that there
         // are two tipes of sides, horizontal and vertical.
@@ -142,7 +188,50 @@ impl CropArea {
             }
         }
 
-        sides.len()
+        Some(sides.len())
+    }
+
+    // The number of corners around the region (holes included), which is the
+    // same as its number of sides.  Counted a cell at a time rather than by
+    // following the sides, so it's an independent check on `sides`.
+    fn corners(&self) -> usize {
+        let member = |x: usize, y: usize, x_off: isize, y_off: isize| match (
+            x.checked_add_signed(x_off),
+            y.checked_add_signed(y_off),
+        ) {
+            (Some(nx), Some(ny)) => self.members.contains(&(nx, ny)),
+            _ => false,
+        };
+
+        let mut corners = 0;
+        for (x, y) in self.members.iter().cloned() {
+            for (x_off, y_off) in DIAGONAL_OFFSETS {
+                let vertical = member(x, y, x_off, 0);
+                let horizontal = member(x, y, 0, y_off);
+                // an outside corner has neither neighbor next to it; an
+                // inside corner has both but not the one between them
+                if (!vertical && !horizontal)
+                    || (vertical && horizontal && !member(x, y, x_off, y_off))
+                {
+                    corners += 1;
+                }
+            }
+        }
+        corners
+    }
+
+    // One line describing the region and both of its prices; None for
+    // 8-connected regions, like `perimeter` and `sides`
+    fn summary(&self) -> Option<String> {
+        Some(format!(
+            "crop '{}': area={} perimeter={} sides={} price={} bulk={}",
+            self.crop,
+            self.area(),
+            self.perimeter()?,
+            self.sides()?,
+            self.price()?,
+            self.bulk_price()?,
+        ))
     }
 }
 
@@ -151,22 +240,27 @@ fn find_adjacent_crops(
     area_crop: char,
     row_idx: usize,
     col_idx: usize,
+    connectivity: Connectivity,
     found: &mut HashSet<(usize, usize)>,
+    visited: &mut HashSet<(usize, usize)>,
 ) {
-    let this_crop = plot[row_idx][col_idx];
-    if this_crop != area_crop {
-        return;
-    }
-
-    found.insert((row_idx, col_idx));
-    for (row_offset, col_offset) in [(-1, 0), (1, 0), (0, 1), (0, -1)] {
-        let next_row_idx = row_idx.checked_add_signed(row_offset);
-        let next_col_idx = col_idx.checked_add_signed(col_offset);
-        if let (Some(next_row_idx), Some(next_col_idx)) = (next_row_idx, next_col_idx) {
-            if next_row_idx < plot.len() && next_col_idx < plot[0].len() {
-                if !found.contains(&(next_row_idx, next_col_idx)) {
-                    // recurse on our neighbors
-                    find_adjacent_crops(plot, area_crop, next_row_idx, next_col_idx, found);
+    // flood fill using an explicit worklist rather than recursion so that
+    // large regions don't blow the stack.
+    let mut worklist = vec![(row_idx, col_idx)];
+    while let Some((row_idx, col_idx)) = worklist.pop() {
+        if plot[row_idx][col_idx] != area_crop || !found.insert((row_idx, col_idx)) {
+            continue;
+        }
+        visited.insert((row_idx, col_idx));
+
+        for (row_offset, col_offset) in connectivity.offsets() {
+            let next_row_idx = row_idx.checked_add_signed(row_offset);
+            let next_col_idx = col_idx.checked_add_signed(col_offset);
+            if let (Some(next_row_idx), Some(next_col_idx)) = (next_row_idx, next_col_idx) {
+                if next_row_idx < plot.len() && next_col_idx < plot[0].len() {
+                    if !found.contains(&(next_row_idx, next_col_idx)) {
+                        worklist.push((next_row_idx, next_col_idx));
+                    }
                 }
             }
         }
@@ -174,45 +268,239 @@ fn find_adjacent_crops(
 }
 
 // iterate through the plot
-fn find_crop_areas(plot: &[Vec<char>]) -> Vec<CropArea> {
+fn find_crop_areas(plot: &[Vec<char>], connectivity: Connectivity) -> Vec<CropArea> {
     let row_count = plot.len();
     let col_count = plot[0].len();
     let mut crop_areas: Vec<CropArea> = Vec::new();
+    // every cell that is already in one of the crop areas
+    let mut visited: HashSet<(usize, usize)> = HashSet::new();
     for row_idx in 0..row_count {
         for col_idx in 0..col_count {
             let crop = plot[row_idx][col_idx];
             // if this position is already accounted for, move past it
-            if crop_areas
-                .iter()
-                .find(|ca| ca.members.contains(&(row_idx, col_idx)))
-                .is_some()
-            {
+            if visited.contains(&(row_idx, col_idx)) {
                 continue;
             }
 
             // we have a new croparea, let's find our friends
             let mut crop_members = HashSet::new();
-            find_adjacent_crops(plot, crop, row_idx, col_idx, &mut crop_members);
+            find_adjacent_crops(
+                plot,
+                crop,
+                row_idx,
+                col_idx,
+                connectivity,
+                &mut crop_members,
+                &mut visited,
+            );
             crop_areas.push(CropArea {
-                _crop: crop,
+                crop,
                 members: crop_members,
                 row_count,
                 col_count,
+                connectivity,
             })
         }
     }
 
+    // each cell should have ended up in exactly one crop area
+    let assigned: usize = crop_areas.iter().map(|ca| ca.members.len()).sum();
+    assert!(
+        assigned == row_count * col_count && visited.len() == assigned,
+        "{assigned} cells assigned to crop areas ({} distinct) in a {row_count}x{col_count} plot",
+        visited.len()
+    );
+
     crop_areas
 }
 
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d12.txt")]
+    input: String,
+
+    #[arg(short, long, value_enum, default_value_t)]
+    part: Part,
+
+    /// Whether plots touching only at a corner belong to the same region;
+    /// prices are only defined for `four`
+    #[arg(short, long, value_enum, default_value_t)]
+    connectivity: Connectivity,
+
+    /// Print the area, perimeter, sides and prices of every region
+    #[arg(short, long)]
+    verbose: bool,
+}
+
 fn main() -> anyhow::Result<()> {
-    let plots = parse_input("d12.txt")?;
-    let crop_areas = find_crop_areas(&plots);
-    let total_price: usize = crop_areas.iter().map(|ca| ca.price()).sum();
-    println!("Total Price: {total_price}");
+    let cli = Cli::parse();
+    let plots = parse_input(&cli.input)?;
+    let crop_areas = find_crop_areas(&plots, cli.connectivity);
+    if cli.connectivity != Connectivity::Four {
+        let largest = crop_areas.iter().map(|ca| ca.area()).max().unwrap_or(0);
+        println!(
+            "Regions: {} (largest area {largest}); prices need 4-connected regions",
+            crop_areas.len()
+        );
+        return Ok(());
+    }
 
-    let bulk_price: usize = crop_areas.iter().map(|ca| ca.bulk_price()).sum();
-    println!("Bulk Price: {bulk_price}"); // 802799 is too low
+    // under --verbose, also total the bulk price from each region's corners
+    // (rather than its sides) as a check on part 2
+    let mut corner_bulk_price = 0;
+    if cli.verbose {
+        for ca in crop_areas.iter() {
+            let summary = ca.summary().context("Summary needs 4-connected regions")?;
+            println!("{summary}");
+            corner_bulk_price += ca.area() * ca.corners();
+        }
+    }
+
+    if cli.part.part1() {
+        let total_price: Option<usize> = crop_areas.iter().map(|ca| ca.price()).sum();
+        let total_price = total_price.context("Perimeter needs 4-connected regions")?;
+        println!("Part 1: {total_price}");
+    }
+    if cli.part.part2() {
+        let bulk_price: Option<usize> = crop_areas.iter().map(|ca| ca.bulk_price()).sum();
+        let bulk_price = bulk_price.context("Sides need 4-connected regions")?;
+        if cli.verbose && corner_bulk_price != bulk_price {
+            anyhow::bail!(
+                "Bulk price is {bulk_price} counting sides but {corner_bulk_price} counting corners"
+            );
+        }
+        println!("Part 2: {bulk_price}"); // 802799 is too low
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (plots, total price, bulk price) for the worked examples in the puzzle
+    const EXAMPLES: [(&str, usize, usize); 5] = [
+        ("AAAA\nBBCD\nBBCC\nEEEC", 140, 80),
+        ("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO", 772, 436),
+        ("EEEEE\nEXXXX\nEEEEE\nEXXXX\nEEEEE", 692, 236),
+        ("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA", 1184, 368),
+        (
+            "RRRRIICCFF\nRRRRIICCCF\nVVRRRCCFFF\nVVRCCCJFFF\nVVVVCJJCFE\n\
+             VVIVCCJJEE\nVVIIICJJEE\nMIIIIIJJEE\nMIIISIJEEE\nMMMISSJEEE",
+            1930,
+            1206,
+        ),
+    ];
+
+    // The A block and the lone A only touch at a corner, as do the two B strips
+    const DIAGONAL_BRIDGE: &str = "AAB\nAAB\nBBA";
+
+    // Every plot is its own region (with 4-connectivity)
+    const CHECKERBOARD: &str = "ABABAB\nBABABA\nABABAB\nBABABA\nABABAB\nBABABA";
+
+    fn parse_plots(plots: &str) -> Vec<Vec<char>> {
+        plots.lines().map(|l| l.chars().collect()).collect()
+    }
+
+    // sorted region sizes for the plots under the given connectivity
+    fn region_areas(plots: &[Vec<char>], connectivity: Connectivity) -> Vec<usize> {
+        let mut areas: Vec<usize> = find_crop_areas(plots, connectivity)
+            .iter()
+            .map(|ca| ca.area())
+            .collect();
+        areas.sort();
+        areas
+    }
+
+    #[test]
+    fn examples() {
+        for (plots, price, bulk_price) in EXAMPLES {
+            let crop_areas = find_crop_areas(&parse_plots(plots), Connectivity::Four);
+            let actual: usize = crop_areas.iter().filter_map(|ca| ca.price()).sum();
+            assert_eq!(actual, price, "{plots}");
+            let actual: usize = crop_areas.iter().filter_map(|ca| ca.bulk_price()).sum();
+            assert_eq!(actual, bulk_price, "{plots}");
+        }
+    }
+
+    #[test]
+    fn diagonal_bridge() {
+        let plots = parse_plots(DIAGONAL_BRIDGE);
+        assert_eq!(region_areas(&plots, Connectivity::Four), [1, 2, 2, 4]);
+        assert_eq!(region_areas(&plots, Connectivity::Eight), [4, 5]);
+        // 8-connected regions have no perimeter or sides
+        for ca in find_crop_areas(&plots, Connectivity::Eight) {
+            assert_eq!(ca.perimeter(), None);
+            assert_eq!(ca.sides(), None);
+        }
+    }
+
+    #[test]
+    fn checkerboard() {
+        let crop_areas = find_crop_areas(&parse_plots(CHECKERBOARD), Connectivity::Four);
+        assert_eq!(crop_areas.len(), 36);
+        let price: usize = crop_areas.iter().filter_map(|ca| ca.price()).sum();
+        assert_eq!(price, 36 * 4);
+    }
+
+    #[test]
+    fn single_crop_filling_the_grid() {
+        // one region spanning the whole of a puzzle sized grid
+        let plots = vec![vec!['A'; 140]; 140];
+        let crop_areas = find_crop_areas(&plots, Connectivity::Four);
+        assert_eq!(crop_areas.len(), 1);
+        let region = &crop_areas[0];
+        assert_eq!(region.area(), 140 * 140);
+        assert_eq!(region.perimeter(), Some(4 * 140));
+        assert_eq!(region.sides(), Some(4));
+    }
+
+    #[test]
+    fn perimeter_edges_of_small_example() {
+        use Direction::*;
+
+        let crop_areas = find_crop_areas(&parse_plots(EXAMPLES[0].0), Connectivity::Four);
+        let c = crop_areas.iter().find(|ca| ca.crop == 'C').unwrap();
+        assert_eq!(
+            c.perimeter_edges(),
+            [
+                (1, 2, Up),
+                (1, 2, Left),
+                (1, 2, Right),
+                (2, 2, Down),
+                (2, 2, Left),
+                (2, 3, Up),
+                (2, 3, Right),
+                (3, 3, Down),
+                (3, 3, Left),
+                (3, 3, Right),
+            ]
+        );
+
+        let edges: usize = crop_areas.iter().map(|ca| ca.perimeter_edges().len()).sum();
+        assert_eq!(edges, 10 + 8 + 10 + 4 + 8);
+    }
+
+    #[test]
+    fn corners_match_sides() {
+        for (plots, _, bulk_price) in EXAMPLES {
+            let crop_areas = find_crop_areas(&parse_plots(plots), Connectivity::Four);
+            for ca in crop_areas.iter() {
+                assert_eq!(ca.sides(), Some(ca.corners()), "{}", ca.summary().unwrap());
+            }
+            let by_corners: usize = crop_areas.iter().map(|ca| ca.area() * ca.corners()).sum();
+            assert_eq!(by_corners, bulk_price, "{plots}");
+        }
+    }
+
+    #[test]
+    fn summary() {
+        let crop_areas = find_crop_areas(&parse_plots(EXAMPLES[0].0), Connectivity::Four);
+        let c = crop_areas.iter().find(|ca| ca.crop == 'C').unwrap();
+        assert_eq!(
+            c.summary().unwrap(),
+            "crop 'C': area=4 perimeter=10 sides=8 price=40 bulk=32"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use aoc::Part;
+use clap::Parser;
 use regex::{Captures, Regex};
 use std::{
     collections::HashSet,
@@ -128,7 +130,7 @@ fn find_optimal_naive(machine: &ClawMachine) -> Option<(usize, usize)> {
 // This should be linear time at least, but does still seem to be
 // slow as we're dealing with big numbers...
 //
-// More math to do, keeping this in for reference.
+// More math to do, keeping this in for reference (and for cross-checking).
 #[allow(unused)]
 fn find_optimal_constrain_single_variable(machine: &ClawMachine) -> Option<(usize, usize)> {
     let mut working: HashSet<(usize, usize)> = HashSet::new();
@@ -141,12 +143,11 @@ fn find_optimal_constrain_single_variable(machine: &ClawMachine) -> Option<(usiz
     while a_hits * machine.a.x <= machine.prize_location.0
         && a_hits * machine.a.y <= machine.prize_location.1
     {
-        if a_hits % 100_000_000 == 0 {
+        if a_hits > 0 && a_hits % 100_000_000 == 0 {
             println!("{a_hits}");
         }
         // solve for b using fixed a
         let b_x = (px_f - a_hits as f64 * mx_a_f) / mx_b_f;
-        println!("b_x = {b_x}");
         if b_x.fract() == 0.0 {
             // there's a solution for b_x that is an integer but that only matters
             // if we end up with that solution being correct for y as well
@@ -191,59 +192,274 @@ fn find_optimal_constrain_single_variable(machine: &ClawMachine) -> Option<(usiz
 //    D(b) = | ax px | = ax * py - ay * px
 //           | ay py |
 //
-// b = D(b) / D = (ax*by - ay*bx) / (ax*py - ay*px)
+// b = D(b) / D = (ax*py - ay*px) / (ax*by - ay*bx)
 //
-// All of those variables are fixed, so we can calculate b and check to see
-// if it is an integer.  If it is, then we just need to see if the computed
-// value for a works out right as well.
-fn find_optimal_using_math(machine: &ClawMachine) -> Option<(usize, usize)> {
-    // just do float conversion up from to clean things up
-    let px = machine.prize_location.0 as f64;
-    let py = machine.prize_location.1 as f64;
-    let ax = machine.a.x as f64;
-    let ay = machine.a.y as f64;
-    let bx = machine.b.x as f64;
-    let by = machine.b.y as f64;
-
-    // do the craamer rule 2x2 calculation shown above
-    let det = ax * by - ay * bx;
-
-    // to see if the solution works as integer, just cast and do a final check; if
-    // we had a float solution then the final checks won't line up
-    let a = ((px * by - py * bx) / det) as usize;
-    let b = ((ax * py - ay * px) / det) as usize;
-
-    if a * machine.a.x + b * machine.b.x == machine.prize_location.0
-        && a * machine.a.y + b * machine.b.y == machine.prize_location.1
-    {
+// All of those variables are fixed, so we can calculate a and b and check
+// that they are (non-negative) integers, which is the case exactly when the
+// numerators divide evenly by D.  Doing this in integers rather than floats
+// means there is no rounding to worry about, even with the part 2 offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CramerWorking {
+    det: i128,
+    a_numerator: i128,
+    b_numerator: i128,
+}
+
+impl CramerWorking {
+    fn new(machine: &ClawMachine) -> Self {
+        let px = machine.prize_location.0 as i128;
+        let py = machine.prize_location.1 as i128;
+        let ax = machine.a.x as i128;
+        let ay = machine.a.y as i128;
+        let bx = machine.b.x as i128;
+        let by = machine.b.y as i128;
+
+        // do the craamer rule 2x2 calculation shown above
+        CramerWorking {
+            det: ax * by - ay * bx,
+            a_numerator: px * by - py * bx,
+            b_numerator: ax * py - ay * px,
+        }
+    }
+
+    fn divisible(&self, numerator: i128) -> bool {
+        self.det != 0 && numerator % self.det == 0
+    }
+
+    // The (a, b) press counts, if they are non-negative integers
+    fn presses(&self) -> Option<(usize, usize)> {
+        if !self.divisible(self.a_numerator) || !self.divisible(self.b_numerator) {
+            return None;
+        }
+        let a = usize::try_from(self.a_numerator / self.det).ok()?;
+        let b = usize::try_from(self.b_numerator / self.det).ok()?;
         Some((a, b))
-    } else {
-        None
     }
 }
 
-fn main() -> anyhow::Result<()> {
-    let mut machines = parse_input("d13.txt")?;
-    let mut tokens = 0;
-    for machine in machines.iter() {
-        if let Some((a, b)) = find_optimal_naive(machine) {
-            tokens += a * 3 + b;
+impl std::fmt::Display for CramerWorking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let divisible = |numerator| match self.divisible(numerator) {
+            true => "divisible",
+            false => "not divisible",
+        };
+        write!(
+            f,
+            "det = {}, a = {} / {} ({}), b = {} / {} ({}): ",
+            self.det,
+            self.a_numerator,
+            self.det,
+            divisible(self.a_numerator),
+            self.b_numerator,
+            self.det,
+            divisible(self.b_numerator)
+        )?;
+        match self.presses() {
+            Some((a, b)) => write!(f, "{a} A presses, {b} B presses"),
+            None => write!(f, "no integer solution"),
         }
     }
-    println!("Part 1 Tokens: {tokens:?}");
+}
 
-    // now add 10000000000000 to X/Y of the inputs for part 2
-    for machine in machines.iter_mut() {
-        machine.prize_location.0 += 10_000_000_000_000;
-        machine.prize_location.1 += 10_000_000_000_000;
+fn find_optimal_using_math(machine: &ClawMachine) -> Option<(usize, usize)> {
+    let (a, b) = CramerWorking::new(machine).presses()?;
+    debug_assert!(
+        a * machine.a.x + b * machine.b.x == machine.prize_location.0
+            && a * machine.a.y + b * machine.b.y == machine.prize_location.1
+    );
+    Some((a, b))
+}
+
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d13.txt")]
+    input: String,
+
+    #[arg(short, long, value_enum, default_value_t)]
+    part: Part,
+
+    /// Print how Cramer's rule works out for each machine
+    #[arg(short, long)]
+    explain: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let machines = parse_input(&cli.input)?;
+    if cli.part.part1() {
+        let mut tokens = 0;
+        for (i, machine) in machines.iter().enumerate() {
+            if cli.explain {
+                println!("Machine {}: {}", i + 1, CramerWorking::new(machine));
+            }
+            if let Some((a, b)) = find_optimal_naive(machine) {
+                tokens += a * 3 + b;
+            }
+        }
+        println!("Part 1: {tokens}");
     }
-    tokens = 0;
-    for machine in machines.iter() {
-        if let Some((a, b)) = find_optimal_using_math(machine) {
-            tokens += a * 3 + b;
+
+    if cli.part.part2() {
+        // now add 10000000000000 to X/Y of the inputs for part 2; done on a
+        // copy of each machine so the parsed machines are left as is.
+        let offset_machines = machines.iter().map(|machine| ClawMachine {
+            prize_location: (
+                machine.prize_location.0 + 10_000_000_000_000,
+                machine.prize_location.1 + 10_000_000_000_000,
+            ),
+            ..machine.clone()
+        });
+        let mut tokens = 0;
+        for (i, machine) in offset_machines.enumerate() {
+            if cli.explain {
+                println!("Machine {}: {}", i + 1, CramerWorking::new(&machine));
+            }
+            if let Some((a, b)) = find_optimal_using_math(&machine) {
+                tokens += a * 3 + b;
+            }
         }
+        println!("Part 2: {tokens}");
     }
-    println!("Part 2 Tokens: {tokens:?}");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc::testing::XorShift;
+
+    // The first two machines from the puzzle's example, one with a prize that
+    // can be won and one without, and how each is explained.
+    const EXAMPLE_EXPLANATIONS: [(ClawMachine, &str); 2] = [
+        (
+            ClawMachine {
+                a: Movement { x: 94, y: 34 },
+                b: Movement { x: 22, y: 67 },
+                prize_location: (8400, 5400),
+            },
+            "det = 5550, a = 444000 / 5550 (divisible), b = 222000 / 5550 (divisible): \
+             80 A presses, 40 B presses",
+        ),
+        (
+            ClawMachine {
+                a: Movement { x: 26, y: 66 },
+                b: Movement { x: 67, y: 21 },
+                prize_location: (12748, 12176),
+            },
+            "det = -3876, a = -548084 / -3876 (not divisible), b = -524792 / -3876 (not divisible): \
+             no integer solution",
+        ),
+    ];
+
+    fn random_machine(rng: &mut XorShift) -> ClawMachine {
+        loop {
+            let a = Movement {
+                x: rng.below(20) + 1,
+                y: rng.below(20) + 1,
+            };
+            let b = Movement {
+                x: rng.below(20) + 1,
+                y: rng.below(20) + 1,
+            };
+
+            // the solvers all assume the buttons aren't collinear (the same
+            // is true of the puzzle input), so skip those.
+            if a.x * b.y == a.y * b.x {
+                continue;
+            }
+
+            // half the time, place the prize at a spot we know is reachable
+            let prize_location = if rng.below(2) == 0 {
+                let (a_hits, b_hits) = (rng.below(10), rng.below(10));
+                (a.x * a_hits + b.x * b_hits, a.y * a_hits + b.y * b_hits)
+            } else {
+                (rng.below(300), rng.below(300))
+            };
+
+            return ClawMachine {
+                a,
+                b,
+                prize_location,
+            };
+        }
+    }
+
+    // Pick random (non-collinear) buttons and press counts and place the prize
+    // wherever that lands, returning the machine along with the press counts.
+    // With press counts up to `max_presses` and buttons moving up to 100 the prize
+    // can be as far out as the part 2 offset.
+    fn random_solvable_machine(
+        rng: &mut XorShift,
+        max_presses: usize,
+    ) -> (ClawMachine, (usize, usize)) {
+        loop {
+            let a = Movement {
+                x: rng.below(100) + 1,
+                y: rng.below(100) + 1,
+            };
+            let b = Movement {
+                x: rng.below(100) + 1,
+                y: rng.below(100) + 1,
+            };
+            if a.x * b.y == a.y * b.x {
+                continue;
+            }
+
+            let (a_hits, b_hits) = (rng.below(max_presses + 1), rng.below(max_presses + 1));
+            let prize_location = (a.x * a_hits + b.x * b_hits, a.y * a_hits + b.y * b_hits);
+            let machine = ClawMachine {
+                a,
+                b,
+                prize_location,
+            };
+            return (machine, (a_hits, b_hits));
+        }
+    }
+
+    // All of the solvers agree on a bunch of small machines where even the
+    // brute force approach is cheap.
+    #[test]
+    fn solvers_agree_on_small_machines() {
+        let mut rng = XorShift(2024);
+        let mut solvable = 0;
+        for _ in 0..500 {
+            let machine = random_machine(&mut rng);
+            let naive = find_optimal_naive(&machine);
+            assert_eq!(
+                find_optimal_constrain_single_variable(&machine),
+                naive,
+                "{machine:?}"
+            );
+            assert_eq!(find_optimal_using_math(&machine), naive, "{machine:?}");
+            if naive.is_some() {
+                solvable += 1;
+            }
+        }
+        // make sure both outcomes were covered
+        assert!(solvable > 0 && solvable < 500);
+    }
+
+    // Only the math solver is fast enough for part 2 sized machines; as the
+    // buttons aren't collinear there is exactly one way to reach the prize,
+    // so it has to find the presses the machine was built from.
+    #[test]
+    fn math_solver_on_large_machines() {
+        let mut rng = XorShift(2024);
+        for _ in 0..500 {
+            let (machine, presses) = random_solvable_machine(&mut rng, 100_000_000_000);
+            assert_eq!(
+                find_optimal_using_math(&machine),
+                Some(presses),
+                "{machine:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn example_explanations() {
+        for (machine, expected) in EXAMPLE_EXPLANATIONS.iter() {
+            assert_eq!(CramerWorking::new(machine).to_string(), *expected);
+        }
+    }
+}
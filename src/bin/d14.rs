@@ -1,6 +1,13 @@
-use std::{collections::HashSet, path::Path};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
 
-use aoc::input_lines;
+use aoc::{cli::CommonArgs, grid::wrap, input_lines};
+use clap::Parser;
 use regex::Regex;
 
 #[derive(Debug, Clone)]
@@ -11,25 +18,41 @@ struct Robot {
     vy: isize,
 }
 
-fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Robot>> {
+// The example is on a smaller grid than the puzzle, so an input can start
+// with a header giving its size, e.g. `# grid 11x7`.
+const DEFAULT_DIMENSIONS: (isize, isize) = (101, 103);
+
+// The robots, along with the (width, height) from the header if there is one.
+fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<(Vec<Robot>, Option<(isize, isize)>)> {
     // example line: p=0,4 v=3,-3
     let robo_re = Regex::new(r"p=(?<x>\d+),(?<y>\d+) v=(?<vx>[-]?\d+),(?<vy>[-]?\d+)")?;
-    let robots = input_lines(path)?.enumerate().filter_map(|(_idx, l) | {
-        let caps = robo_re.captures(&l)?;
-        let x = caps.name("x")?.as_str().parse().ok()?;
-        let y = caps.name("y")?.as_str().parse().ok()?;
-        let vx = caps.name("vx")?.as_str().parse().ok()?;
-        let vy = caps.name("vy")?.as_str().parse().ok()?;
-        Some(Robot {
-            x,
-            y,
-            vx,
-            vy,
-        })
-    })
-    .collect();
+    let header_re = Regex::new(r"^#\s*grid\s+(?<width>\d+)x(?<height>\d+)\s*$")?;
+    let mut dimensions = None;
+    let mut robots = Vec::new();
+    for (idx, l) in input_lines(path)?.enumerate() {
+        if idx == 0 {
+            if let Some(caps) = header_re.captures(&l) {
+                let width: isize = caps["width"].parse()?;
+                let height: isize = caps["height"].parse()?;
+                if width == 0 || height == 0 {
+                    anyhow::bail!("Grid in the header must not be empty: {l:?}");
+                }
+                dimensions = Some((width, height));
+                continue;
+            }
+        }
+        let Some(caps) = robo_re.captures(&l) else {
+            continue;
+        };
+        robots.push(Robot {
+            x: caps["x"].parse()?,
+            y: caps["y"].parse()?,
+            vx: caps["vx"].parse()?,
+            vy: caps["vy"].parse()?,
+        });
+    }
 
-    Ok(robots)
+    Ok((robots, dimensions))
 }
 
 #[derive(Debug)]
@@ -40,16 +63,8 @@ struct RobotPosition {
 
 fn simulate_robot(robot: &mut Robot, xmax: isize, ymax: isize, seconds: usize) {
     for _ in 0..seconds {
-        robot.x = (robot.x + robot.vx) % xmax;
-        robot.y = (robot.y + robot.vy) % ymax;
-
-        if robot.x < 0 {
-            robot.x += xmax;
-        }
-
-        if robot.y < 0 {
-            robot.y += ymax;
-        }
+        robot.x = wrap(robot.x + robot.vx, xmax);
+        robot.y = wrap(robot.y + robot.vy, ymax);
     }
 }
 
@@ -74,6 +89,110 @@ fn simulate(robots: &mut [Robot], xmax: isize /* cols */, ymax: isize /* rows */
     positions
 }
 
+// Closed form for where a robot will be after some number of seconds; no
+// need to step through each second as the motion is linear with wraparound.
+fn position_at(robot: &Robot, xmax: isize, ymax: isize, seconds: usize) -> RobotPosition {
+    let t = seconds as isize;
+    RobotPosition {
+        x: wrap(robot.x + robot.vx * t, xmax),
+        y: wrap(robot.y + robot.vy * t, ymax),
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// After this many seconds every robot is back where it started
+fn period(xmax: isize, ymax: isize) -> usize {
+    let (xmax, ymax) = (xmax as usize, ymax as usize);
+    xmax / gcd(xmax, ymax) * ymax
+}
+
+// Write the safety factor at each tick across a full period as `tick,safety`
+fn write_safety_series<W: Write>(
+    out: &mut W,
+    robots: &[Robot],
+    xmax: isize,
+    ymax: isize,
+) -> anyhow::Result<()> {
+    writeln!(out, "tick,safety")?;
+    for tick in 0..period(xmax, ymax) {
+        let positions: Vec<RobotPosition> = robots
+            .iter()
+            .map(|robot| position_at(robot, xmax, ymax, tick))
+            .collect();
+        let safety = compute_safety_factory(&positions, xmax, ymax);
+        writeln!(out, "{tick},{safety}")?;
+    }
+    Ok(())
+}
+
+// The lowest safety factor over a full period along with every tick that
+// reaches it; the robots are most clustered (e.g. into a tree) at those ticks.
+fn min_safety_ticks(robots: &[Robot], xmax: isize, ymax: isize) -> (usize, Vec<usize>) {
+    let mut min_safety = usize::MAX;
+    let mut ticks = Vec::new();
+    for tick in 0..period(xmax, ymax) {
+        let positions: Vec<RobotPosition> = robots
+            .iter()
+            .map(|robot| position_at(robot, xmax, ymax, tick))
+            .collect();
+        let safety = compute_safety_factory(&positions, xmax, ymax);
+        if safety < min_safety {
+            min_safety = safety;
+            ticks.clear();
+        }
+        if safety == min_safety {
+            ticks.push(tick);
+        }
+    }
+    (min_safety, ticks)
+}
+
+// Draw the grid with the number of robots on each tile ('.' for none)
+fn render(positions: &[RobotPosition], xmax: isize, ymax: isize) -> String {
+    let mut counts = vec![vec![0; xmax as usize]; ymax as usize];
+    for p in positions {
+        counts[p.y as usize][p.x as usize] += 1;
+    }
+
+    let mut s = String::new();
+    for row in counts {
+        for present in row {
+            if present == 0 {
+                s.push('.');
+            } else {
+                s.push_str(&present.to_string());
+            }
+        }
+        s.push('\n');
+    }
+    s
+}
+
+fn clear_screen() {
+    print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
+}
+
+// Show the robots at each second from 0 up to and including `seconds`
+fn animate(robots: &[Robot], xmax: isize, ymax: isize, seconds: usize, delay: Duration) {
+    for tick in 0..=seconds {
+        let positions: Vec<RobotPosition> = robots
+            .iter()
+            .map(|robot| position_at(robot, xmax, ymax, tick))
+            .collect();
+        clear_screen();
+        print!("{}", render(&positions, xmax, ymax));
+        println!("Seconds={tick}");
+        std::thread::sleep(delay);
+    }
+}
+
 fn is_christmas_tree(positions: &[RobotPosition], _xmax: isize, _ymax: isize) -> bool {
     // let's guess that maybe an interation where there are no overlapping robots
     // might be an easter egg given the density of bots...
@@ -81,63 +200,214 @@ fn is_christmas_tree(positions: &[RobotPosition], _xmax: isize, _ymax: isize) ->
     pos_set.len() == positions.len()
 }
 
+// Which half of an axis of length `max` a coordinate falls in: `Some(false)`
+// for the low half, `Some(true)` for the high half and `None` for the middle.
+//
+// Only odd lengths have a middle row/col (which doesn't count toward any
+// quadrant); an even length splits cleanly into two equal halves, e.g. for 10
+// the halves are 0..5 and 5..10.
+fn half(v: isize, max: isize) -> Option<bool> {
+    if v < max / 2 {
+        Some(false)
+    } else if v >= (max + 1) / 2 {
+        Some(true)
+    } else {
+        None
+    }
+}
+
 fn compute_safety_factory(positions: &[RobotPosition], xmax: isize, ymax: isize) -> usize {
     let mut tl = 0;
     let mut tr = 0;
     let mut bl = 0;
     let mut br = 0;
-    let xmid = ((xmax - 1) / 2) as isize;
-    let ymid = ((ymax - 1) / 2) as isize;
     for pos in positions {
-        // left side
-        if pos.x < xmid {
-            if pos.y < ymid {
-                tl += 1;
-            }
-            if pos.y > ymid {
-                bl += 1;
-            }
-        }
-
-        // right side
-        if pos.x > xmid {
-            if pos.y < ymid {
-                tr += 1;
-            }
-            if pos.y > ymid {
-                br += 1;
-            }
+        match (half(pos.x, xmax), half(pos.y, ymax)) {
+            (Some(false), Some(false)) => tl += 1,
+            (Some(true), Some(false)) => tr += 1,
+            (Some(false), Some(true)) => bl += 1,
+            (Some(true), Some(true)) => br += 1,
+            _ => {} // on a midline
         }
     }
 
     tl * tr * bl * br
 }
 
+#[derive(Debug, Parser)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Width of the grid, if the input has no `# grid WxH` header (defaults
+    /// to 101)
+    #[arg(long)]
+    width: Option<isize>,
+
+    /// Height of the grid, if the input has no `# grid WxH` header (defaults
+    /// to 103)
+    #[arg(long)]
+    height: Option<isize>,
+
+    /// Write the safety factor for each tick of a full period as CSV to this
+    /// file (or `-` for stdout) instead of searching for the tree
+    #[arg(long)]
+    csv: Option<String>,
+
+    /// How many seconds to animate (with --animate, which draws the robots
+    /// each second instead of solving)
+    #[arg(long, default_value_t = 100)]
+    seconds: usize,
+}
+
 fn main() -> anyhow::Result<()> {
-    let mut robots = parse_input("d14.txt")?;
-    let xmax = 101;
-    let ymax = 103;
-
-    let mut seconds = 1;
-    let mut positions = simulate(&mut robots, xmax, ymax, seconds);
-    while !is_christmas_tree(&positions, xmax, ymax) {
-        positions = simulate(&mut robots, xmax, ymax, 1);
-        seconds += 1;
-    }
-    println!("\nSeconds={seconds}, Positions={positions:?}");
-    for y in 0..ymax {
-        for x in 0..xmax {
-            let present = positions.iter().filter(|p| p.x == x && p.y == y).count();
-            if present == 0 {
-                print!(".");
-            } else {
-                print!("{present}");
-            }
+    let cli = Cli::parse();
+    let (mut robots, header_dimensions) = parse_input(cli.common.input_or("d14.txt"))?;
+    let (xmax, ymax) = header_dimensions.unwrap_or((
+        cli.width.unwrap_or(DEFAULT_DIMENSIONS.0),
+        cli.height.unwrap_or(DEFAULT_DIMENSIONS.1),
+    ));
+
+    if let Some(csv) = &cli.csv {
+        if csv == "-" {
+            write_safety_series(&mut std::io::stdout().lock(), &robots, xmax, ymax)?;
+        } else {
+            let mut out = BufWriter::new(File::create(csv)?);
+            write_safety_series(&mut out, &robots, xmax, ymax)?;
+        }
+        return Ok(());
+    }
+
+    if cli.common.animate {
+        animate(&robots, xmax, ymax, cli.seconds, cli.common.delay());
+        return Ok(());
+    }
+
+    if cli.common.part.part1() {
+        let safety_factor = cli.common.timed("Part 1", || {
+            let positions: Vec<RobotPosition> = robots
+                .iter()
+                .map(|robot| position_at(robot, xmax, ymax, 100))
+                .collect();
+            compute_safety_factory(&positions, xmax, ymax)
+        });
+        println!("Part 1: {safety_factor}");
+    }
+
+    if !cli.common.part.part2() {
+        return Ok(());
+    }
+
+    let original_robots = robots.clone();
+    let (seconds, positions) = cli.common.timed("Part 2", || {
+        let mut seconds = 1;
+        let mut positions = simulate(&mut robots, xmax, ymax, seconds);
+        while !is_christmas_tree(&positions, xmax, ymax) {
+            positions = simulate(&mut robots, xmax, ymax, 1);
+            seconds += 1;
         }
-        println!("");
+        (seconds, positions)
+    });
+    println!("\nSeconds={seconds}");
+    if cli.common.verbose {
+        println!("Positions={positions:?}");
     }
+    print!("{}", render(&positions, xmax, ymax));
     let sf = compute_safety_factory(&positions, xmax, ymax);
     println!("Safety Factory: {sf}");
-    println!("Easter Egg @ {seconds} seconds");
+
+    // The tree should be the one tick in the period where the robots are the
+    // most clustered; if another tick is as good, we may have a false positive.
+    let (min_safety, min_ticks) = min_safety_ticks(&original_robots, xmax, ymax);
+    if min_ticks != [seconds % period(xmax, ymax)] {
+        println!(
+            "Warning: tick {seconds} is not the only tick with the minimum safety factor \
+             ({min_safety}) over the period of {}; check ticks {min_ticks:?}",
+            period(xmax, ymax)
+        );
+    }
+    println!("Part 2: {seconds}");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The header gives the example's grid size, which the safety factor
+    // depends on.
+    #[test]
+    fn example_header() {
+        let (robots, dimensions) = parse_input("d14-example1.txt").unwrap();
+        assert_eq!(dimensions, Some((11, 7)));
+        assert_eq!(robots.len(), 12);
+        let positions: Vec<RobotPosition> = robots
+            .iter()
+            .map(|robot| position_at(robot, 11, 7, 100))
+            .collect();
+        assert_eq!(compute_safety_factory(&positions, 11, 7), 12);
+    }
+
+    // On a 10x6 grid there is no middle column or row: x = 4 and y = 2 are
+    // the last of the low halves and x = 5 and y = 3 the first of the high
+    // ones.  On 11x7, x = 5 and y = 3 are the midlines and count for nothing.
+    #[test]
+    fn even_dimension_quadrants() {
+        let at = |x, y| RobotPosition { x, y };
+        let positions = [at(4, 2), at(5, 2), at(4, 3), at(5, 3), at(5, 3)];
+        assert_eq!(compute_safety_factory(&positions, 10, 6), 2);
+        assert_eq!(compute_safety_factory(&positions, 11, 7), 0);
+
+        assert_eq!(half(4, 10), Some(false));
+        assert_eq!(half(5, 10), Some(true));
+        assert_eq!(half(2, 6), Some(false));
+        assert_eq!(half(3, 6), Some(true));
+        assert_eq!(half(5, 11), None);
+        assert_eq!(half(3, 7), None);
+    }
+
+    // The example robots are all back where they started after lcm(11, 7) =
+    // 77 seconds, and not all of them are at any tick before that.
+    #[test]
+    fn example_period() {
+        let (robots, _) = parse_input("d14-example1.txt").unwrap();
+        let positions = |tick| -> Vec<(isize, isize)> {
+            robots
+                .iter()
+                .map(|robot| position_at(robot, 11, 7, tick))
+                .map(|p| (p.x, p.y))
+                .collect()
+        };
+        assert_eq!(period(11, 7), 77);
+        let start = positions(0);
+        assert_eq!(positions(77), start);
+        assert_eq!(positions(2 * 77 + 5), positions(5));
+        for tick in 1..77 {
+            assert_ne!(positions(tick), start, "tick {tick}");
+        }
+    }
+
+    // Sixty robots, one for each velocity, that all meet at (1, 1) at tick
+    // 30, plus one parked in each corner so that no quadrant is ever empty.
+    // Gathering everything into one quadrant gives the lowest safety factor,
+    // 61 * 1 * 1 * 1, and only at that tick.
+    #[test]
+    fn clustered_tick() {
+        const TICK: isize = 30;
+        let mut robots: Vec<Robot> = [(0, 0), (10, 0), (0, 6), (10, 6)]
+            .into_iter()
+            .map(|(x, y)| Robot { x, y, vx: 0, vy: 0 })
+            .collect();
+        for vx in 1..11 {
+            for vy in 1..7 {
+                robots.push(Robot {
+                    x: wrap(1 - vx * TICK, 11),
+                    y: wrap(1 - vy * TICK, 7),
+                    vx,
+                    vy,
+                });
+            }
+        }
+        assert_eq!(min_safety_ticks(&robots, 11, 7), (61, vec![TICK as usize]));
+    }
+}
@@ -1,6 +1,7 @@
-use std::{collections::{HashSet, VecDeque}, fmt::Display, path::Path, time::Duration};
+use std::{collections::{HashSet, VecDeque}, fmt::Display, path::{Path, PathBuf}, sync::OnceLock, time::Duration};
 
-use aoc::input_lines;
+use aoc::{input_lines, Part};
+use clap::Parser;
 
 #[derive(Debug, Clone, Copy)]
 struct Position {
@@ -129,45 +130,33 @@ fn find_robot(map: &Map) -> Position {
     panic!("Where's our robot?");
 }
 
+// Push the chain of boxes starting at (box_x, box_y) one step along the
+// delta, returning whether there was room to.  Rather than recursing once per
+// box, walk to the end of the chain: a wall means nothing moves, an empty
+// space means every box moves along into it.
 fn shift_boxes(map: &mut Map, box_x: usize, box_y: usize, delta_x: isize, delta_y: isize) -> bool {
-    let next_x = (box_x as isize + delta_x) as usize;
-    let next_y = (box_y as isize + delta_y) as usize;
-    let next_obj = map[next_y][next_x];
-    let cur = map[box_y][box_x];
-    match next_obj {
-        Object::Empty => {
-            map[box_y][box_x] = Object::Empty;
-            map[next_y][next_x] = cur;
-            true
-        }
-        Object::Wall => {
-            false
-        }
-        Object::Robot => {
-            panic!("Didn't expect that")
-        }
-        Object::BoxLeft | Object::BoxRight => {
-            if delta_y == 0 {
-                // We can treat left/right movement the same as a regular box
-                let pushed = shift_boxes(map, next_x, next_y, delta_x, delta_y);
-                if pushed {
-                    map[box_y][box_x] = Object::Empty;
-                    map[next_y][next_x] = cur;
-                }
-                pushed
-            } else {
-                panic!("This case not handled here")
-            }
-        }
-        Object::Box => {
-            let pushed = shift_boxes(map, next_x, next_y, delta_x, delta_y);
-            if pushed {
-                map[box_y][box_x] = Object::Empty;
-                map[next_y][next_x] = Object::Box;
-            }
-            pushed
+    let (mut end_x, mut end_y) = (box_x, box_y);
+    loop {
+        match map[end_y][end_x] {
+            Object::Box => {}
+            // We can treat left/right movement the same as a regular box
+            Object::BoxLeft | Object::BoxRight if delta_y == 0 => {}
+            Object::BoxLeft | Object::BoxRight => panic!("This case not handled here"),
+            Object::Empty => break,
+            Object::Wall => return false,
+            Object::Robot => panic!("Didn't expect that"),
         }
+        (end_x, end_y) = next_position(map, end_x, end_y, delta_x, delta_y);
+    }
+
+    // move each box along, starting from the one next to the space
+    while (end_x, end_y) != (box_x, box_y) {
+        let (prev_x, prev_y) = next_position(map, end_x, end_y, -delta_x, -delta_y);
+        map[end_y][end_x] = map[prev_y][prev_x];
+        (end_x, end_y) = (prev_x, prev_y);
     }
+    map[box_y][box_x] = Object::Empty;
+    true
 }
 
 fn next_position(map: &Map, x: usize, y: usize, delta_x: isize, delta_y: isize) -> (usize, usize) {
@@ -275,7 +264,57 @@ fn shift(map: &mut Map, x: usize, y: usize, delta_x: isize, delta_y: isize) {
     map[y][x] = Object::Empty;
 }
 
-fn simulate(map: &mut Map, movements: &[Movement]) {
+// Number of boxes in the line starting at (x, y) heading in the direction of
+// the delta; wide boxes count once.
+fn boxes_in_line(map: &Map, x: usize, y: usize, delta_x: isize, delta_y: isize) -> usize {
+    let (mut x, mut y) = (x, y);
+    let mut count = 0;
+    loop {
+        match map[y][x] {
+            Object::Box | Object::BoxLeft => count += 1,
+            Object::BoxRight => {}
+            _ => return count,
+        }
+        (x, y) = next_position(map, x, y, delta_x, delta_y);
+    }
+}
+
+/// How the robot got on with its list of movements.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct MoveStats {
+    total: usize,
+    advanced: usize,
+    /// moves where a wall was in the way, either directly or behind boxes
+    blocked: usize,
+    boxes_pushed: usize,
+    /// GPS total kept up to date as boxes are pushed, to check against a
+    /// full recount of the final map
+    gps: usize,
+}
+
+impl Display for MoveStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Moves: {} total, {} advanced, {} blocked by walls, {} boxes pushed",
+            self.total, self.advanced, self.blocked, self.boxes_pushed
+        )
+    }
+}
+
+// Run the robot through each of the movements, calling `on_move` with the
+// state of the map after each move.  Any rendering or recording of frames is
+// left up to the caller.
+fn simulate<F>(map: &mut Map, movements: &[Movement], mut on_move: F) -> MoveStats
+where
+    F: FnMut(usize, &Movement, &Map),
+{
+    let mut stats = MoveStats {
+        total: movements.len(),
+        ..Default::default()
+    };
+    // every box pushed moves its GPS coordinate by the same amount
+    let mut gps = compute_gps(map) as isize;
     let mut robo = find_robot(map);
     for (i, movement) in movements.iter().enumerate() {
         let (delta_x, delta_y) = match movement {
@@ -294,19 +333,28 @@ fn simulate(map: &mut Map, movements: &[Movement]) {
                 robo.x = next_x;
                 robo.y = next_y;
                 map[robo.y][robo.x] = Object::Robot;
+                stats.advanced += 1;
             }
             Object::Wall => {
                 // do nothing; robot doesn't get to move.
+                stats.blocked += 1;
             }
             Object::Box | Object::BoxLeft | Object::BoxRight => {
-                // potentially shift box(es) by delta
-                if delta_y == 0 {
+                // potentially shift box(es) by delta; single width boxes
+                // (part 1) can be pushed the same way in any direction.
+                if delta_y == 0 || obj_at_next_pos == Object::Box {
+                    let boxes = boxes_in_line(map, next_x, next_y, delta_x, delta_y);
                     let shifted = shift_boxes(map, next_x, next_y, delta_x, delta_y);
                     if shifted {
                         map[robo.y][robo.x] = Object::Empty;
                         robo.x = next_x;
                         robo.y = next_y;
                         map[robo.y][robo.x] = Object::Robot;
+                        stats.advanced += 1;
+                        stats.boxes_pushed += boxes;
+                        gps += boxes as isize * (100 * delta_y + delta_x);
+                    } else {
+                        stats.blocked += 1;
                     }
                 } else {
                     // need to do a dfs to see if we can shift
@@ -336,6 +384,12 @@ fn simulate(map: &mut Map, movements: &[Movement]) {
                         robo.x = next_x;
                         robo.y = next_y;
                         map[robo.y][robo.x] = Object::Robot;
+                        stats.advanced += 1;
+                        // each box is two cells, both of which were moved
+                        stats.boxes_pushed += moved.len() / 2;
+                        gps += (moved.len() / 2) as isize * 100 * delta_y;
+                    } else {
+                        stats.blocked += 1;
                     }
                 }
             }
@@ -344,14 +398,52 @@ fn simulate(map: &mut Map, movements: &[Movement]) {
             }
         }
 
-        println!("Enter for next...");
-        let mut _s = String::new();
-        // std::io::stdin().read_line(&mut _s).unwrap();
-        std::thread::sleep(Duration::from_millis(5));
-        clear_screen();
-        println!("Movement    {movement} ({} / {})", i + 1, movements.len());
-        print_map(map);
+        on_move(i, movement, map);
+    }
+    stats.gps = gps as usize;
+    stats
+}
+
+fn map_to_string(map: &Map) -> String {
+    let mut s = String::new();
+    for row in map.iter() {
+        s.extend(row.iter().map(|obj| obj.as_char()));
+        s.push('\n');
+    }
+    s
+}
+
+// Simulate, animating and/or recording each frame as requested on the CLI.
+// Frames are written out as they're produced; the first failure to write one
+// stops the recording and is reported once the simulation is done.
+fn run_simulation(map: &mut Map, movements: &[Movement], part: &str) -> anyhow::Result<MoveStats> {
+    let cli = cli();
+    let record_dir = cli.record.as_ref().map(|dir| dir.join(part));
+    if let Some(dir) = &record_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut record_error = None;
+    let stats = simulate(map, movements, |i, movement, map| {
+        if cli.animate {
+            std::thread::sleep(Duration::from_millis(cli.delay_animation_ms));
+            clear_screen();
+            println!("Movement    {movement} ({} / {})", i + 1, movements.len());
+            print_map(map);
+        }
+        if let (Some(dir), true) = (&record_dir, record_error.is_none()) {
+            let frame = dir.join(format!("frame-{i:05}.txt"));
+            record_error = std::fs::write(frame, map_to_string(map)).err();
+        }
+    });
+
+    if let Some(err) = record_error {
+        return Err(err.into());
     }
+    if let Some(dir) = &record_dir {
+        println!("Recorded {} frames to {}", movements.len(), dir.display());
+    }
+
+    Ok(stats)
 }
 
 fn compute_gps(map: &Map) -> usize {
@@ -366,29 +458,176 @@ fn compute_gps(map: &Map) -> usize {
     gps_sum
 }
 
-#[allow(unused)]
+// The GPS total of the final map, making sure it agrees with the total kept
+// while simulating; a mismatch means a box was lost or duplicated by a push.
+fn checked_gps(map: &Map, stats: &MoveStats) -> anyhow::Result<usize> {
+    let gps = compute_gps(map);
+    if gps != stats.gps {
+        anyhow::bail!(
+            "GPS of the final map is {gps} but the running total is {}",
+            stats.gps
+        );
+    }
+    Ok(gps)
+}
+
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d15.txt")]
+    input: String,
+
+    #[arg(short, long, value_enum, default_value_t)]
+    part: Part,
+
+    #[arg(short, long, action)]
+    animate: bool,
+
+    #[arg(short, long, default_value_t = 5)]
+    delay_animation_ms: u64,
+
+    /// Directory to write each frame of the simulation to as text (in a
+    /// subdirectory per part)
+    #[arg(short, long)]
+    record: Option<PathBuf>,
+}
+
+fn cli() -> &'static Cli {
+    static CLI: OnceLock<Cli> = OnceLock::new();
+    CLI.get_or_init(Cli::parse)
+}
+
 fn part1() -> anyhow::Result<()> {
-    let (mut map, movements) = parse_input("d15.txt", false)?;
-    clear_screen();
+    let (mut map, movements) = parse_input(&cli().input, false)?;
+    if cli().animate {
+        clear_screen();
+    }
     println!("Initial Map ({} moves)", movements.len());
     print_map(&map);
-    simulate(&mut map, &movements);
-    println!("GPS: {}", compute_gps(&map));
+    let stats = run_simulation(&mut map, &movements, "part1")?;
+    print_map(&map);
+    println!("Part 1: {}", checked_gps(&map, &stats)?);
+    println!("{stats}");
     Ok(())
 }
 
 fn part2() -> anyhow::Result<()> {
-    let (mut map, movements) = parse_input("d15.txt", true)?;
-    clear_screen();
+    let (mut map, movements) = parse_input(&cli().input, true)?;
+    if cli().animate {
+        clear_screen();
+    }
     println!("Initial Map ({} moves)", movements.len());
     print_map(&map);
-    simulate(&mut map, &movements);
-    println!("GPS: {}", compute_gps(&map));
+    let stats = run_simulation(&mut map, &movements, "part2")?;
+    print_map(&map);
+    println!("Part 2: {}", checked_gps(&map, &stats)?);
+    println!("{stats}");
     Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
-    // part1()?;
-    part2()?;
+    if cli().part.part1() {
+        part1()?;
+    }
+    if cli().part.part2() {
+        part2()?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Where the robot and boxes end up in the smaller example, which the last
+    // frame handed to the callback should show.
+    const SMALL_EXAMPLE_FINAL: &str = "\
+########
+#....OO#
+##.....#
+#.....O#
+#.#O@..#
+#...O..#
+#...O..#
+########
+";
+
+    #[test]
+    fn small_example() {
+        let (mut map, movements) = parse_input("d15-example1.txt", false).unwrap();
+        let mut frames = Vec::new();
+        let stats = simulate(&mut map, &movements, |_, _, map| frames.push(map_to_string(map)));
+        assert_eq!(frames.len(), movements.len());
+        assert_eq!(frames.last().unwrap(), SMALL_EXAMPLE_FINAL);
+        assert_eq!(checked_gps(&map, &stats).unwrap(), 2028);
+    }
+
+    #[test]
+    fn small_example_stats() {
+        // Two of the moves in the puzzle's walkthrough walk straight into a
+        // wall and three more would push boxes into one.
+        let (mut map, movements) = parse_input("d15-example1.txt", false).unwrap();
+        let stats = simulate(&mut map, &movements, |_, _, _| {});
+        let expected = MoveStats {
+            total: 15,
+            advanced: 10,
+            blocked: 5,
+            boxes_pushed: 10,
+            gps: 2028,
+        };
+        assert_eq!(stats, expected);
+        assert_eq!(
+            stats.to_string(),
+            "Moves: 15 total, 10 advanced, 5 blocked by walls, 10 boxes pushed"
+        );
+    }
+
+    #[test]
+    fn larger_example() {
+        for (part2, expected) in [(false, 10092), (true, 9021)] {
+            let (mut map, movements) = parse_input("d15-example2.txt", part2).unwrap();
+            let stats = simulate(&mut map, &movements, |_, _, _| {});
+            assert_eq!(checked_gps(&map, &stats).unwrap(), expected, "part 2: {part2}");
+        }
+    }
+
+    // A single row with the robot behind a chain of boxes, optionally with a
+    // space between the chain and the wall at the end.
+    const CHAIN_LENGTH: usize = 20;
+
+    fn chain_row(wide: bool, space: bool) -> Vec<Object> {
+        let boxes: &[Object] = if wide {
+            &[Object::BoxLeft, Object::BoxRight]
+        } else {
+            &[Object::Box]
+        };
+        let mut row = vec![Object::Wall, Object::Robot];
+        for _ in 0..CHAIN_LENGTH {
+            row.extend_from_slice(boxes);
+        }
+        if space {
+            row.push(Object::Empty);
+        }
+        row.push(Object::Wall);
+        row
+    }
+
+    #[test]
+    fn box_chains() {
+        for wide in [false, true] {
+            // into the wall, nothing moves
+            let mut map = vec![chain_row(wide, false)];
+            let stats = simulate(&mut map, &[Movement::Right], |_, _, _| {});
+            assert_eq!(map, vec![chain_row(wide, false)], "wide: {wide}");
+            assert_eq!(stats.blocked, 1);
+
+            // into the space, the robot and every box move along one
+            let mut map = vec![chain_row(wide, true)];
+            let stats = simulate(&mut map, &[Movement::Right], |_, _, _| {});
+            let mut expected = chain_row(wide, false);
+            expected.insert(1, Object::Empty);
+            assert_eq!(map, vec![expected], "wide: {wide}");
+            assert_eq!(stats.boxes_pushed, CHAIN_LENGTH);
+            checked_gps(&map, &stats).unwrap();
+        }
+    }
+}
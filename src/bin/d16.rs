@@ -3,13 +3,18 @@ use std::{
     fmt::Display,
     ops::{Deref, DerefMut},
     path::Path,
+    process::ExitCode,
     sync::OnceLock,
 };
 
-use aoc::input_lines;
+use aoc::{
+    cli::CommonArgs,
+    direction::{Direction, DirectionSet},
+    input_lines,
+};
 use clap::Parser;
 use colored::Colorize;
-use dijkstra::{Vertex, DIRECTIONS};
+use dijkstra::Vertex;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum MapItem {
@@ -17,15 +22,8 @@ enum MapItem {
     Empty,
     Start,
     End,
-    Reindeer(HashSet<Direction>),
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
+    Reindeer(DirectionSet),
+    Seat, // a tile on at least one of the optimal paths
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -35,45 +33,6 @@ struct Reindeer {
     direction: Direction,
 }
 
-impl Direction {
-    fn as_char(&self) -> char {
-        match self {
-            Self::Up => '^',
-            Self::Down => 'v',
-            Self::Left => '<',
-            Self::Right => '>',
-        }
-    }
-
-    fn dx_dy(&self) -> (isize, isize) {
-        match self {
-            Self::Up => (0, -1),
-            Self::Down => (0, 1),
-            Self::Left => (-1, 0),
-            Self::Right => (1, 0),
-        }
-    }
-
-    fn opposite_direction(&self) -> Direction {
-        match self {
-            Self::Up => Self::Down,
-            Self::Down => Self::Up,
-            Self::Left => Self::Right,
-            Self::Right => Self::Left,
-        }
-    }
-
-    fn turns_to_face(&self, other: Direction) -> usize {
-        if self == &other {
-            0
-        } else if self.opposite_direction() == other {
-            2
-        } else {
-            1
-        }
-    }
-}
-
 impl MapItem {
     fn as_char(&self) -> char {
         match self {
@@ -81,6 +40,7 @@ impl MapItem {
             Self::Empty => '.',
             Self::Start => 'S',
             Self::End => 'E',
+            Self::Seat => 'O',
             Self::Reindeer(dirs) => match dirs.len() {
                 1 => dirs.iter().nth(0).unwrap().as_char(),
                 _ => '+',
@@ -96,10 +56,10 @@ impl From<char> for MapItem {
             '.' => Self::Empty,
             'S' => Self::Start,
             'E' => Self::End,
-            '>' => Self::Reindeer(HashSet::from([Direction::Right])),
-            'v' => Self::Reindeer(HashSet::from([Direction::Down])),
-            '<' => Self::Reindeer(HashSet::from([Direction::Left])),
-            '^' => Self::Reindeer(HashSet::from([Direction::Up])),
+            '>' => Self::Reindeer(DirectionSet::only(Direction::Right)),
+            'v' => Self::Reindeer(DirectionSet::only(Direction::Down)),
+            '<' => Self::Reindeer(DirectionSet::only(Direction::Left)),
+            '^' => Self::Reindeer(DirectionSet::only(Direction::Up)),
             c => panic!("Unknown char {c}"),
         }
     }
@@ -110,6 +70,7 @@ impl Display for MapItem {
         let c = self.as_char();
         match self {
             Self::Reindeer(_) => write!(f, "{}", format!("{c}").blue()),
+            Self::Seat => write!(f, "{}", format!("{c}").green()),
             _ => write!(f, "{c}"),
         }
     }
@@ -132,6 +93,33 @@ impl Deref for Map {
     }
 }
 
+impl Map {
+    // The neighboring cell in `direction`, or None if that is off the map;
+    // puzzle mazes are walled in but smaller ones may not be.
+    fn step(&self, x: usize, y: usize, direction: Direction) -> Option<(usize, usize)> {
+        let (dx, dy) = direction.dx_dy();
+        let nx = x.checked_add_signed(dx)?;
+        let ny = y.checked_add_signed(dy)?;
+        if ny < self.len() && nx < self[ny].len() {
+            Some((nx, ny))
+        } else {
+            None
+        }
+    }
+
+    /// Every in bounds, non-wall neighbor of (x, y) along with the direction
+    /// moved to get there.
+    fn passable_neighbors(&self, x: usize, y: usize) -> Vec<(usize, usize, Direction)> {
+        Direction::ALL
+            .into_iter()
+            .filter_map(|direction| {
+                let (nx, ny) = self.step(x, y, direction)?;
+                (self[ny][nx] != MapItem::Wall).then_some((nx, ny, direction))
+            })
+            .collect()
+    }
+}
+
 impl Display for Map {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for row in self.0.iter() {
@@ -145,10 +133,25 @@ impl Display for Map {
 }
 
 fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Map> {
-    let map: Vec<Vec<MapItem>> = input_lines(path)?
+    parse_map(input_lines(path)?)
+}
+
+fn parse_map(lines: impl Iterator<Item = String>) -> anyhow::Result<Map> {
+    let map: Vec<Vec<MapItem>> = lines
         .map(|line| line.chars().map(|c| MapItem::from(c)).collect())
         .collect();
 
+    // the solver needs exactly one start and one end to search between
+    for (item, name) in [(MapItem::Start, "start"), (MapItem::End, "end")] {
+        let count = map.iter().flatten().filter(|i| **i == item).count();
+        if count != 1 {
+            anyhow::bail!(
+                "Maze must have exactly one {name} ('{}'), found {count}",
+                item.as_char()
+            );
+        }
+    }
+
     Ok(Map(map))
 }
 
@@ -187,7 +190,8 @@ mod dijkstra {
 
     impl Ord for Vertex {
         fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-            self.x.cmp(&other.x)
+            self.x
+                .cmp(&other.x)
                 .then_with(|| self.y.cmp(&other.y))
                 .then_with(|| self.direction.cmp(&other.direction))
         }
@@ -202,18 +206,13 @@ mod dijkstra {
     struct Edge {
         next_position: Vertex,
         cost: usize,
+        /// Vertices passed through on the way to `next_position`; only
+        /// populated for edges that stand in for a contracted corridor
+        via: Vec<Vertex>,
     }
 
-    pub const DIRECTIONS: [Direction; 4] = [
-        Direction::Up,
-        Direction::Down,
-        Direction::Left,
-        Direction::Right,
-    ];
-
     #[derive(Debug, Clone, PartialEq, Eq)]
     pub struct State {
-        pub path: Vec<Vertex>,
         pub position: Vertex,
         pub cost: usize,
     }
@@ -233,43 +232,92 @@ mod dijkstra {
         }
     }
 
-    fn build_adjancy_map(map: &Map) -> HashMap<Vertex, Vec<Edge>> {
-        let mut adjacencies: HashMap<Vertex, Vec<Edge>> = HashMap::new();
-        for (y, row) in map.iter().enumerate() {
-            for (x, val) in row.iter().enumerate() {
-                if matches!(val, MapItem::Empty | MapItem::Start | MapItem::End) {
-                    for cur_direction in DIRECTIONS {
-                        let mut adjacent = Vec::with_capacity(4);
-                        for move_direction in DIRECTIONS {
-                            let (dx, dy) = move_direction.dx_dy();
-                            let (nx, ny) = ((x as isize + dx) as usize, (y as isize + dy) as usize);
-                            let nmap = &map[ny][nx];
-                            if *nmap == MapItem::Wall {
-                                continue; // not really an edge here
-                            }
-                            let number_turns_to_face = cur_direction.turns_to_face(move_direction);
-                            let edge_cost = number_turns_to_face * 1000 + 1;
-                            let edge = Edge {
-                                next_position: Vertex {
-                                    x: nx,
-                                    y: ny,
-                                    direction: move_direction,
-                                },
-                                cost: edge_cost,
-                            };
-                            adjacent.push(edge);
-                        }
-                        let this_vertex = Vertex {
-                            x,
-                            y,
-                            direction: cur_direction,
-                        };
-                        adjacencies.insert(this_vertex, adjacent);
-                    }
-                }
-            }
+    #[derive(Debug, Clone)]
+    pub struct Solution {
+        /// Cost of the optimal route(s)
+        pub cost: usize,
+
+        /// One optimal route from the start to the end, reconstructed from
+        /// the predecessors recorded while searching
+        pub path: Vec<Vertex>,
+
+        /// Every vertex that is on at least one optimal route
+        pub optimal_vertices: HashSet<Vertex>,
+    }
+
+    // Single step edges out of `vertex`: a move to each open neighbor,
+    // turning to face it first if needed.
+    fn step_edges(map: &Map, vertex: Vertex, turn_cost: usize, step_cost: usize) -> Vec<Edge> {
+        let Vertex { x, y, direction } = vertex;
+        map.passable_neighbors(x, y)
+            .into_iter()
+            .map(|(nx, ny, move_direction)| Edge {
+                next_position: Vertex {
+                    x: nx,
+                    y: ny,
+                    direction: move_direction,
+                },
+                cost: direction.turns_to_face(move_direction) * turn_cost + step_cost,
+                via: Vec::new(),
+            })
+            .collect()
+    }
+
+    // Every open cell is a node in the contracted graph except for plain
+    // corridor cells, which have exactly one way in and one way out.
+    fn is_node(map: &Map, x: usize, y: usize) -> bool {
+        map[y][x] != MapItem::Empty || map.passable_neighbors(x, y).len() != 2
+    }
+
+    // Walk from the node at (x, y) heading in `direction` until we reach the
+    // next node, returning each vertex visited (ending with that node) along
+    // with the cost of the walk, not counting any turn needed to set off.
+    fn follow_corridor(
+        map: &Map,
+        x: usize,
+        y: usize,
+        direction: Direction,
+        turn_cost: usize,
+        step_cost: usize,
+    ) -> (Vec<Vertex>, usize) {
+        let (mut x, mut y) = map
+            .step(x, y, direction)
+            .expect("passable neighbors stay on the map");
+        let mut direction = direction;
+        let mut walked = vec![Vertex { x, y, direction }];
+        let mut cost = step_cost;
+        while !is_node(map, x, y) {
+            let (nx, ny, next_direction) = map
+                .passable_neighbors(x, y)
+                .into_iter()
+                .find(|&(_, _, d)| d != direction.opposite_direction())
+                .expect("corridor cells have a way out");
+            cost += direction.turns_to_face(next_direction) * turn_cost + step_cost;
+            direction = next_direction;
+            (x, y) = (nx, ny);
+            walked.push(Vertex { x, y, direction });
         }
-        adjacencies
+        (walked, cost)
+    }
+
+    // Like `step_edges` but only junctions, dead ends, the start and the end
+    // are vertices; each corridor between them is a single weighted edge that
+    // remembers the cells it passes through.
+    fn corridor_edges(map: &Map, vertex: Vertex, turn_cost: usize, step_cost: usize) -> Vec<Edge> {
+        let Vertex { x, y, direction } = vertex;
+        map.passable_neighbors(x, y)
+            .into_iter()
+            .map(|(_, _, corridor_direction)| {
+                let (mut walked, cost) =
+                    follow_corridor(map, x, y, corridor_direction, turn_cost, step_cost);
+                let next_position = walked.pop().unwrap();
+                Edge {
+                    next_position,
+                    cost: direction.turns_to_face(corridor_direction) * turn_cost + cost,
+                    via: walked,
+                }
+            })
+            .collect()
     }
 
     // Using Dijkstra's algorithm to find the lowest cost path
@@ -285,51 +333,66 @@ mod dijkstra {
     // - z: The direction we are facing when we moved to the vertex
     //
     // The weight on the edges between adjacent nodes in the graph are the associated
-    // cost which will be 1 plus the 1000 * number of turns to get to the required z
-    // value for the node.  We include the modeling of a direct move backwards (though
-    // we could safely preclude this case) as this will always have a cost of 2002 and
-    // wouldn't ever realistically be selected.
+    // cost which will be the step cost plus the turn cost * number of turns to get to
+    // the required z value for the node (1 and 1000 in the puzzle).  We include the
+    // modeling of a direct move backwards (though we could safely preclude this case)
+    // as this always costs two turns plus a step and wouldn't ever realistically be
+    // selected.
+    //
+    // Most of the maze is long corridors where there is no choice to be made,
+    // so by default those are contracted into single edges between junctions
+    // as we go; `contract = false` searches the full graph instead.  Edges are
+    // worked out from the map as each vertex is expanded rather than built up
+    // front, and the best known cost to each vertex lives in a dense
+    // [y][x][direction] array.
     pub fn find_optimal_path_using_dijkstra(
         map: &Map,
-    ) -> Option<Vec<State>> {
-        let adjacencies = build_adjancy_map(map);
-        let mut dist: HashMap<Vertex, usize> = HashMap::new();
-        let mut prev: HashMap<Vertex, Vertex> = HashMap::new();
+        turn_cost: usize,
+        step_cost: usize,
+        contract: bool,
+    ) -> Option<Solution> {
+        let edges_from = |vertex: Vertex| {
+            if contract {
+                corridor_edges(map, vertex, turn_cost, step_cost)
+            } else {
+                step_edges(map, vertex, turn_cost, step_cost)
+            }
+        };
+        let mut dist: Vec<Vec<[usize; 4]>> = map
+            .iter()
+            .map(|row| vec![[usize::MAX; 4]; row.len()])
+            .collect();
+        let mut prev: HashMap<Vertex, Vec<(Vertex, Vec<Vertex>)>> = HashMap::new();
         let mut pq = BinaryHeap::new();
 
-        for vertex in adjacencies.keys() {
-            dist.insert(*vertex, usize::MAX);
-        }
-
         let rudolph = find_rudolph(map);
         let rudolph_position = Vertex {
             x: rudolph.x,
             y: rudolph.y,
             direction: rudolph.direction,
         };
-        dist.insert(rudolph_position, 0);
+        dist[rudolph.y][rudolph.x][rudolph.direction.idx()] = 0;
         pq.push(State {
-            path: Vec::from(&[rudolph_position]),
             position: rudolph_position,
             cost: 0,
         });
 
-        let mut solutions: Vec<State> = Vec::new();
+        let mut best_cost: Option<usize> = None;
+        let mut ends: Vec<Vertex> = Vec::new();
 
         // examine the "frontier" with lowest cost nodes first
-        while let Some(State { position, cost, path }) = pq.pop() {
+        while let Some(State { position, cost }) = pq.pop() {
             let Vertex { x, y, .. } = position;
 
             // If we've reached the end, we've found an optimal route; for part
-            // 2 we want to find the count of spots along any of the optimal
-            // routes.
+            // 2 we want to find all of the spots along any of the optimal
+            // routes, so keep going until the cost goes up.
             if map[y][x] == MapItem::End {
-                if solutions.is_empty() || cost == solutions[0].cost {
-                    solutions.push(State {
-                        path,
-                        position,
-                        cost,
-                    });
+                if best_cost.is_none() || best_cost == Some(cost) {
+                    best_cost = Some(cost);
+                    if !ends.contains(&position) {
+                        ends.push(position);
+                    }
                     continue;
                 } else {
                     break;
@@ -338,47 +401,240 @@ mod dijkstra {
 
             // If we've found a better way, don't use this one but if it is
             // equivalent or the same, continue
-            if cost > dist[&position] {
+            if cost > dist[y][x][position.direction.idx()] {
                 continue;
             }
 
             // for each adjacent node (which we can find out by consulting the map),
             // see if there's a lower cost route.
-            for edge in adjacencies[&position].iter() {
-                let mut new_path = path.clone();
-                new_path.push(edge.next_position);
+            for edge in edges_from(position) {
                 let next = State {
-                    path: new_path,
                     position: edge.next_position,
                     cost: edge.cost + cost,
                 };
 
-                if next.cost <= dist[&next.position] {
-                    dist.insert(next.position, next.cost);
-                    prev.insert(next.position, position);
-                    pq.push(next);
+                // record every predecessor that gets us here at the lowest cost
+                // seen so far; equal cost routes are all kept.
+                let pred = (position, edge.via);
+                let Vertex { x, y, direction } = next.position;
+                let best = &mut dist[y][x][direction.idx()];
+                if next.cost < *best {
+                    prev.insert(next.position, vec![pred]);
+                } else if next.cost == *best {
+                    let preds = prev.entry(next.position).or_default();
+                    if preds.contains(&pred) {
+                        continue; // already expanded along this edge
+                    }
+                    preds.push(pred);
+                } else {
+                    continue;
                 }
+
+                *best = next.cost;
+                pq.push(next);
             }
         }
 
-        if solutions.len() > 0 {
-            Some(solutions)
-        } else {
-            None
+        let cost = best_cost?;
+
+        // walk the first recorded predecessor back from the end to the start,
+        // expanding any corridors that were contracted along the way
+        let mut path = vec![ends[0]];
+        while let Some(preds) = prev.get(path.last().unwrap()) {
+            let (pred, via) = &preds[0];
+            path.extend(via.iter().rev());
+            path.push(*pred);
         }
+        path.reverse();
+
+        // and walk all of the predecessors to find every vertex on any route
+        let mut optimal_vertices: HashSet<Vertex> = HashSet::new();
+        let mut worklist = ends.clone();
+        while let Some(vertex) = worklist.pop() {
+            if optimal_vertices.insert(vertex) {
+                for (pred, via) in prev.get(&vertex).into_iter().flatten() {
+                    optimal_vertices.extend(via.iter());
+                    worklist.push(*pred);
+                }
+            }
+        }
+
+        Some(Solution {
+            cost,
+            path,
+            optimal_vertices,
+        })
     }
+
+    // Dijkstra's run to completion over every (cell, direction), giving the
+    // lowest cost of reaching each reachable cell facing whichever way is
+    // cheapest.  Unlike `find_optimal_path_using_dijkstra` nothing about the
+    // routes is kept, and the end is a cell like any other.
+    pub fn costs_to_all(
+        map: &Map,
+        turn_cost: usize,
+        step_cost: usize,
+    ) -> HashMap<(usize, usize), usize> {
+        let mut dist: Vec<Vec<[usize; 4]>> = map
+            .iter()
+            .map(|row| vec![[usize::MAX; 4]; row.len()])
+            .collect();
+        let mut pq = BinaryHeap::new();
+
+        let rudolph = find_rudolph(map);
+        dist[rudolph.y][rudolph.x][rudolph.direction.idx()] = 0;
+        pq.push(State {
+            position: Vertex {
+                x: rudolph.x,
+                y: rudolph.y,
+                direction: rudolph.direction,
+            },
+            cost: 0,
+        });
+
+        while let Some(State { position, cost }) = pq.pop() {
+            let Vertex { x, y, direction } = position;
+            if cost > dist[y][x][direction.idx()] {
+                continue;
+            }
+
+            for edge in step_edges(map, position, turn_cost, step_cost) {
+                let next_cost = cost + edge.cost;
+                let Vertex { x, y, direction } = edge.next_position;
+                let best = &mut dist[y][x][direction.idx()];
+                if next_cost < *best {
+                    *best = next_cost;
+                    pq.push(State {
+                        position: edge.next_position,
+                        cost: next_cost,
+                    });
+                }
+            }
+        }
+
+        let mut costs = HashMap::new();
+        for (y, row) in dist.iter().enumerate() {
+            for (x, by_direction) in row.iter().enumerate() {
+                let best = *by_direction.iter().min().unwrap();
+                if best != usize::MAX {
+                    costs.insert((x, y), best);
+                }
+            }
+        }
+        costs
+    }
+
+    // Recompute the cost of a route by turns and steps taken
+    pub fn path_cost(path: &[Vertex], turn_cost: usize, step_cost: usize) -> usize {
+        path.windows(2)
+            .map(|pair| pair[0].direction.turns_to_face(pair[1].direction) * turn_cost + step_cost)
+            .sum()
+    }
+}
+
+// Overlay the route on the map, drawing the direction the reindeer was
+// facing on each cell it moved through.
+fn render_path(map: &Map, path: &[Vertex]) -> Map {
+    let mut rendered = map.clone();
+    for vertex in path {
+        let item = &mut rendered[vertex.y][vertex.x];
+        match item {
+            MapItem::Reindeer(directions) => {
+                directions.insert(vertex.direction);
+            }
+            MapItem::Empty => *item = MapItem::Reindeer(DirectionSet::only(vertex.direction)),
+            _ => {} // leave the start and end markers in place
+        }
+    }
+    rendered
+}
+
+// Mark every tile that is on at least one optimal path as a seat, leaving
+// the start and end markers in place.
+fn render_seats(map: &Map, tiles: &HashSet<(usize, usize)>) -> Map {
+    let mut rendered = map.clone();
+    for &(x, y) in tiles {
+        let item = &mut rendered[y][x];
+        if *item == MapItem::Empty {
+            *item = MapItem::Seat;
+        }
+    }
+    rendered
+}
+
+// An "x,y" pair, as taken by --cost-to
+fn parse_cell(s: &str) -> Result<(usize, usize), String> {
+    let (x, y) = s.split_once(',').ok_or("expected x,y")?;
+    let x = x.trim().parse().map_err(|e| format!("bad x: {e}"))?;
+    let y = y.trim().parse().map_err(|e| format!("bad y: {e}"))?;
+    Ok((x, y))
+}
+
+// The error for a maze that parses fine but has no way through; main gives
+// it an exit code of its own to keep it apart from errors such as a bad maze,
+// which exit with 1.
+#[derive(Debug)]
+struct NoPath;
+
+impl Display for NoPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No path exists from the start (S) to the end (E)")
+    }
+}
+
+impl std::error::Error for NoPath {}
+
+const NO_PATH_EXIT_CODE: u8 = 2;
+
+// Search for the optimal routes through the maze, checking that the one
+// reconstructed from the search costs what the search said it would.
+fn solve(
+    map: &Map,
+    turn_cost: usize,
+    step_cost: usize,
+    contract: bool,
+) -> anyhow::Result<dijkstra::Solution> {
+    let solution = dijkstra::find_optimal_path_using_dijkstra(map, turn_cost, step_cost, contract)
+        .ok_or(NoPath)?;
+    let path_cost = dijkstra::path_cost(&solution.path, turn_cost, step_cost);
+    if path_cost != solution.cost {
+        anyhow::bail!(
+            "Reconstructed path cost {path_cost} does not match optimal cost {}",
+            solution.cost
+        );
+    }
+    Ok(solution)
 }
 
 #[derive(Debug, Parser)]
 struct Cli {
-    #[arg(short, long, default_value = "d16.txt")]
-    input: String,
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Cost of each 90 degree turn
+    #[arg(long, default_value_t = 1000)]
+    turn_cost: usize,
+
+    /// Cost of each step forward
+    #[arg(long, default_value_t = 1)]
+    step_cost: usize,
+
+    /// Search every (cell, direction) rather than contracting corridors
+    #[arg(long)]
+    no_contract: bool,
+
+    /// Also draw every tile on any of the optimal paths (the part 2 seats)
+    #[arg(long)]
+    show_seats: bool,
 
-    #[arg(short, long, action)]
-    animate: bool,
+    /// Only print the answers, one per line without the "Part N:" labels
+    #[arg(short, long)]
+    quiet: bool,
 
-    #[arg(short, long, default_value_t = 5)]
-    delay_animation_ms: u64,
+    /// Report the lowest cost of reaching this cell (as x,y) instead of
+    /// solving
+    #[arg(long, value_parser = parse_cell)]
+    cost_to: Option<(usize, usize)>,
 }
 
 fn cli() -> &'static Cli {
@@ -386,36 +642,238 @@ fn cli() -> &'static Cli {
     CLI.get_or_init(|| Cli::parse())
 }
 
-fn main() -> anyhow::Result<()> {
-    let map = parse_input(&cli().input)?;
+fn main() -> anyhow::Result<ExitCode> {
+    let map = parse_input(cli().common.input_or("d16.txt"))?;
+
+    if let Some((x, y)) = cli().cost_to {
+        let costs = dijkstra::costs_to_all(&map, cli().turn_cost, cli().step_cost);
+        match costs.get(&(x, y)) {
+            Some(cost) => println!("Cost to {x},{y}: {cost}"),
+            None => println!("{x},{y} can't be reached"),
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let quiet = cli().quiet;
+
+    // both parts come out of the same search
+    let solution = cli().common.timed("Search", || {
+        solve(&map, cli().turn_cost, cli().step_cost, !cli().no_contract)
+    });
+    let solution = match solution {
+        Err(e) if e.is::<NoPath>() => {
+            println!("{e}");
+            return Ok(ExitCode::from(NO_PATH_EXIT_CODE));
+        }
+        result => result?,
+    };
+    let unique_locations: HashSet<(usize, usize)> = solution
+        .optimal_vertices
+        .iter()
+        .map(|v| (v.x, v.y))
+        .collect();
+
+    if !quiet {
+        print!("{}", render_path(&map, &solution.path));
+    }
+
+    if cli().show_seats && !quiet {
+        print!("{}", render_seats(&map, &unique_locations));
+        println!("Seats: {}", unique_locations.len());
+    }
+
+    if !quiet {
+        println!("Optimal Path Steps: {}", solution.path.len() - 1);
+    }
+    if cli().common.part.part1() {
+        if quiet {
+            println!("{}", solution.cost);
+        } else {
+            println!("Part 1: {}", solution.cost);
+        }
+    }
+    if cli().common.part.part2() {
+        if quiet {
+            println!("{}", unique_locations.len());
+        } else {
+            println!("Part 2: {}", unique_locations.len());
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let solutions = dijkstra::find_optimal_path_using_dijkstra(&map).unwrap();
-    let paths: Vec<Vec<Vertex>> = solutions.iter().map(|sol| sol.path.clone()).collect();
-    let unique_locations: HashSet<(usize, usize)> = paths.iter().flat_map(|p| p.iter().map(|v| (v.x, v.y))).collect();
+    fn maze(s: &str) -> Map {
+        Map(s
+            .lines()
+            .map(|line| line.chars().map(MapItem::from).collect())
+            .collect())
+    }
 
-    println!("Paths: {:?}", paths.len());
+    // A maze with no wall around its edge, so the search has to stop at the
+    // sides of the map rather than at a wall.
+    #[test]
+    fn borderless_maze() {
+        let map = parse_input("d16-example7.txt").unwrap();
+        let solution = dijkstra::find_optimal_path_using_dijkstra(&map, 1000, 1, true).unwrap();
+        assert_eq!(solution.cost, 1004);
+        assert_eq!(solution.optimal_vertices.len(), 5);
+    }
 
-    for (y, row) in map.iter().enumerate() {
-        for (x, entry) in row.iter().enumerate() {
-            let directions: Vec<Direction> = DIRECTIONS
+    // The number of tiles on any optimal path through the first two examples,
+    // from the puzzle.  The start and end are among them but keep their own
+    // markers when the seats are drawn.
+    const EXAMPLE_SEATS: [(&str, usize); 2] = [("d16-example1.txt", 45), ("d16-example2.txt", 64)];
+
+    #[test]
+    fn example_seats() {
+        for (input, expected) in EXAMPLE_SEATS {
+            let map = parse_input(input).unwrap();
+            let solution = dijkstra::find_optimal_path_using_dijkstra(&map, 1000, 1, true).unwrap();
+            let tiles: HashSet<(usize, usize)> = solution
+                .optimal_vertices
                 .iter()
-                .filter(|&&d| {
-                    let key = Vertex { x, y, direction: d };
-                    paths.iter().filter(|p| p.contains(&key)).count() > 0
-                })
-                .cloned()
+                .map(|v| (v.x, v.y))
                 .collect();
-            match directions.len() {
-                0 => print!("{entry}"),
-                1 => print!("{}", format!("{}", directions[0].as_char()).blue()),
-                _ => print!("{}", format!("+").red()),
+            assert_eq!(tiles.len(), expected, "{input}");
+
+            let seats = render_seats(&map, &tiles);
+            let count = |wanted: MapItem| seats.iter().flatten().filter(|i| **i == wanted).count();
+            assert_eq!(count(MapItem::Seat), expected - 2, "{input}");
+            assert_eq!(count(MapItem::Start), 1, "{input}");
+            assert_eq!(count(MapItem::End), 1, "{input}");
+        }
+    }
+
+    // The first example with the end sealed in by walls.
+    const WALLED_END_MAZE: &str = "\
+###############
+#.......#..####
+#.#.###.#.##E##
+#.....#.#...###
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############";
+
+    #[test]
+    fn walled_off_end() {
+        let map = maze(WALLED_END_MAZE);
+        for contract in [true, false] {
+            let err = solve(&map, 1000, 1, contract).unwrap_err();
+            assert!(err.is::<NoPath>(), "contract: {contract}, got {err}");
+        }
+    }
+
+    #[test]
+    fn missing_end() {
+        let lines = WALLED_END_MAZE.replace('E', ".");
+        let err = parse_map(lines.lines().map(String::from)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Maze must have exactly one end ('E'), found 0"
+        );
+    }
+
+    // A small maze where (1, 2) is a junction with open cells above, below and
+    // to the right of it.
+    const JUNCTION_MAZE: &str = "#####\n#.#E#\n#...#\n#S#.#\n#####";
+
+    #[test]
+    fn junction_neighbors() {
+        let map = maze(JUNCTION_MAZE);
+        let mut neighbors = map.passable_neighbors(1, 2);
+        neighbors.sort();
+        let mut expected = vec![
+            (1, 1, Direction::Up),
+            (1, 3, Direction::Down),
+            (2, 2, Direction::Right),
+        ];
+        expected.sort();
+        assert_eq!(neighbors, expected);
+    }
+
+    // Costing every cell should put the end of the first example the same
+    // distance away as the optimal path.
+    #[test]
+    fn example_costs() {
+        let map = parse_input("d16-example1.txt").unwrap();
+        let end = map
+            .iter()
+            .enumerate()
+            .find_map(|(y, row)| row.iter().position(|i| *i == MapItem::End).map(|x| (x, y)))
+            .unwrap();
+        let costs = dijkstra::costs_to_all(&map, 1000, 1);
+        let solution = dijkstra::find_optimal_path_using_dijkstra(&map, 1000, 1, true).unwrap();
+        assert_eq!(costs.get(&end), Some(&7036));
+        assert_eq!(solution.cost, 7036);
+    }
+
+    // Number of steps on the shortest route from S to E, ignoring which way
+    // the reindeer is facing.
+    fn bfs_distance(map: &Map) -> Option<usize> {
+        let find = |wanted: MapItem| {
+            map.iter()
+                .enumerate()
+                .find_map(|(y, row)| row.iter().position(|i| *i == wanted).map(|x| (x, y)))
+        };
+        let (start, end) = (find(MapItem::Start)?, find(MapItem::End)?);
+        let mut seen = HashSet::from([start]);
+        let mut frontier = vec![start];
+        for steps in 0.. {
+            if frontier.is_empty() {
+                return None;
             }
+            if frontier.contains(&end) {
+                return Some(steps);
+            }
+            frontier = frontier
+                .iter()
+                .flat_map(|&(x, y)| map.passable_neighbors(x, y))
+                .map(|(x, y, _)| (x, y))
+                .filter(|cell| seen.insert(*cell))
+                .collect();
         }
-        println!("");
+        unreachable!()
     }
 
-    println!("Optimal Path Cost: {}", solutions[0].cost);
-    println!("Good Picnic Spots: {}", unique_locations.len());
+    // With turning free, the cheapest route is just the shortest one.
+    #[test]
+    fn free_turns_cost_the_bfs_distance() {
+        let map = parse_input("d16-example1.txt").unwrap();
+        let distance = bfs_distance(&map).unwrap();
+        for contract in [true, false] {
+            let solution =
+                dijkstra::find_optimal_path_using_dijkstra(&map, 0, 1, contract).unwrap();
+            assert_eq!(solution.cost, distance, "contract: {contract}");
+        }
+    }
 
-    Ok(())
+    // Contracting corridors is only a speed up; both searches should find the
+    // same optimal routes.
+    #[test]
+    fn contracted_matches_full_graph() {
+        for (input, _) in EXAMPLE_SEATS {
+            let map = parse_input(input).unwrap();
+            let contracted =
+                dijkstra::find_optimal_path_using_dijkstra(&map, 1000, 1, true).unwrap();
+            let full = dijkstra::find_optimal_path_using_dijkstra(&map, 1000, 1, false).unwrap();
+            assert_eq!(contracted.cost, full.cost, "{input}");
+            assert_eq!(
+                contracted.optimal_vertices, full.optimal_vertices,
+                "{input}"
+            );
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use std::{ops::Deref, path::Path};
 
-use aoc::input_lines;
+use aoc::{input_lines, Part};
 use clap::Parser;
 
 #[derive(Debug)]
@@ -16,6 +16,16 @@ pub enum Instruction {
     Cdv = 7, // Division to C register
 }
 
+impl Instruction {
+    // whether the operand is a combo operand rather than a literal
+    fn takes_combo_operand(&self) -> bool {
+        matches!(
+            self,
+            Self::Adv | Self::Bst | Self::Out | Self::Bdv | Self::Cdv
+        )
+    }
+}
+
 impl From<u8> for Instruction {
     fn from(value: u8) -> Self {
         if value <= Self::Cdv as u8 {
@@ -45,8 +55,11 @@ pub struct Machine {
     reg_c: isize,
 }
 
-fn print_output(out: &[u8]) {
-    println!("{}", out.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(","));
+fn format_output(out: &[u8]) -> String {
+    out.iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
 }
 
 impl Machine {
@@ -56,11 +69,34 @@ impl Machine {
     }
 
     fn execute(&mut self, program: &[u8]) -> Vec<u8> {
+        self.execute_bounded(program, None, None, false)
+    }
+
+    // Run the program until it halts or until either of the (optional) bounds
+    // on the number of outputs or instructions executed is reached; the
+    // machine can be resumed with another call if stopped early.  With trace
+    // set, each instruction is printed along with the registers before and
+    // after it runs.
+    fn execute_bounded(
+        &mut self,
+        program: &[u8],
+        max_output: Option<usize>,
+        max_steps: Option<usize>,
+        trace: bool,
+    ) -> Vec<u8> {
         let mut output: Vec<u8> = Vec::new();
+        let mut steps = 0;
         while let (Some(&instruction_value), Some(&operand)) = (
             program.get(self.instruction_pointer),
             program.get(self.instruction_pointer + 1),
         ) {
+            if max_output.is_some_and(|max| output.len() >= max)
+                || max_steps.is_some_and(|max| steps >= max)
+            {
+                break;
+            }
+            steps += 1;
+            let before = trace.then(|| self.clone());
             self.instruction_pointer += 2;
             let instruction = Instruction::from(instruction_value);
             match instruction {
@@ -77,10 +113,34 @@ impl Machine {
                 Instruction::Bdv => self.reg_b = self.div(operand),
                 Instruction::Cdv => self.reg_c = self.div(operand),
             }
+            if let Some(before) = before {
+                println!("{}", self.trace_step(&before, &instruction, operand));
+            }
         }
         output
     }
 
+    // One line of trace output for an instruction that took the machine
+    // from `before` to its current state.
+    fn trace_step(&self, before: &Machine, instruction: &Instruction, operand: u8) -> String {
+        let operand = if instruction.takes_combo_operand() {
+            format!("{operand} (combo = {})", before.combo_value(operand))
+        } else {
+            operand.to_string()
+        };
+        format!(
+            "ip={:>2} {:?} {operand:<14} {} -> {}",
+            before.instruction_pointer,
+            instruction,
+            before.registers(),
+            self.registers()
+        )
+    }
+
+    fn registers(&self) -> String {
+        format!("A={} B={} C={}", self.reg_a, self.reg_b, self.reg_c)
+    }
+
     fn combo_value(&self, operand: u8) -> isize {
         match operand {
             0 | 1 | 2 | 3 => operand as isize,
@@ -104,22 +164,38 @@ fn parse_reg<I: Iterator<Item = String>>(lines: &mut I) -> anyhow::Result<isize>
     Ok(value_str.trim().parse::<isize>()?)
 }
 
+// Parse a line like "Program: 0,1,5,4,3,0"; whitespace around the values and
+// a trailing comma are allowed.
+fn parse_program(line: &str) -> anyhow::Result<Vec<u8>> {
+    let (_, values) = line
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Malformed program line {line:?}"))?;
+    let values = values.trim();
+    let values = values.strip_suffix(',').unwrap_or(values);
+    if values.is_empty() {
+        anyhow::bail!("Program is empty");
+    }
+    values
+        .split(',')
+        .enumerate()
+        .map(|(i, value)| match value.trim().parse::<u8>() {
+            Ok(opcode) if opcode <= Instruction::Cdv as u8 => Ok(opcode),
+            _ => Err(anyhow::anyhow!(
+                "Invalid program value {value:?} at position {i}"
+            )),
+        })
+        .collect()
+}
+
 fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<(Machine, Vec<u8>)> {
     let mut lines = input_lines(path)?;
     let reg_a = parse_reg(&mut lines)?;
     let reg_b = parse_reg(&mut lines)?;
     let reg_c = parse_reg(&mut lines)?;
-    let _ = lines.next();
-    let program = lines
-        .next()
-        .unwrap()
-        .split(":")
-        .nth(1)
-        .unwrap()
-        .trim()
-        .split(",")
-        .map(|opcode| opcode.parse::<u8>().unwrap())
-        .collect();
+    let program_line = lines
+        .find(|line| !line.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Missing program line"))?;
+    let program = parse_program(&program_line)?;
 
     let machine = Machine {
         instruction_pointer: 0,
@@ -134,15 +210,29 @@ fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<(Machine, Vec<u8>)> {
 struct Cli {
     #[clap(short, long, default_value = "d17.txt")]
     input: String,
+
+    #[clap(short, long, value_enum, default_value_t)]
+    part: Part,
+
+    /// Stop part 1 after the program has produced this many outputs
+    #[clap(long)]
+    max_output: Option<usize>,
+
+    /// Stop part 1 after this many instructions have been executed
+    #[clap(long)]
+    max_steps: Option<usize>,
+
+    /// Print each instruction part 1 executes with the registers before and
+    /// after
+    #[clap(long)]
+    trace: bool,
 }
 
-fn part1() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+fn part1(cli: &Cli, machine: &Machine, program: &[u8]) -> anyhow::Result<()> {
     println!("== Part 1 ==");
-    println!("Input: {}", cli.input);
-    let (mut machine, program) = parse_input(&cli.input)?;
-    let out = machine.execute(&program);
-    print_output(&out);
+    let mut machine = machine.clone();
+    let out = machine.execute_bounded(program, cli.max_output, cli.max_steps, cli.trace);
+    println!("Part 1: {}", format_output(&out));
     println!("");
     Ok(())
 }
@@ -171,23 +261,25 @@ fn part1() -> anyhow::Result<()> {
 // 3. Given this, for each output value we just need to solve for
 //    the lowest bits of a in chunks and then reconstitute the final value.
 
-fn part2() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+fn part2(original_machine: &Machine, program: &[u8]) -> anyhow::Result<()> {
     println!("== Part 2 ==");
-    println!("Input: {}", cli.input);
-    let (original_machine, program) = parse_input(&cli.input)?;
     let mut saved: Vec<isize> = Vec::new();
 
     let run_with_a = |a: isize| {
         let mut machine = original_machine.clone();
         machine.reg_a = a;
-        machine.execute(&program)
+        machine.execute(program)
     };
 
-    // brute force for an initial value for the lowest bits of a
+    // brute force for an initial value for the lowest bits of a; programs
+    // that aren't shaped like the puzzle's may output fewer than two values
+    // for all of these
+    if program.len() < 2 {
+        anyhow::bail!("Program is too short to be a quine candidate");
+    }
     for a in 0..1024 {
         let out = run_with_a(a);
-        if out[0] == program[0] && out[1] == program[1] {
+        if out.len() >= 2 && out[..2] == program[..2] {
             saved.push(a);
         }
     }
@@ -214,16 +306,157 @@ fn part2() -> anyhow::Result<()> {
         saved = next;
     }
 
-    let min = *saved.iter().min().unwrap();
+    let min = *saved.iter().min().ok_or_else(|| {
+        anyhow::anyhow!("No value of A reproduces the program; it isn't a quine candidate")
+    })?;
+    let output = run_with_a(min);
     println!("Program: {program:?}");
-    println!("Output:  {:?}", run_with_a(min));
-    println!("Min: {min}");
+    println!("Output:  {output:?}");
+    if output != program {
+        anyhow::bail!("A={min} does not reproduce the program");
+    }
+    println!("Part 2: {min}");
 
     Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
-    part1()?;
-    part2()?;
+    let cli = Cli::parse();
+    let (machine, program) = parse_input(&cli.input)?;
+
+    println!("Input: {}", cli.input);
+    if cli.part.part1() {
+        part1(&cli, &machine, &program)?;
+    }
+    if cli.part.part2() {
+        part2(&machine, &program)?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc::testing::XorShift;
+
+    fn machine(reg_a: isize) -> Machine {
+        Machine {
+            instruction_pointer: 0,
+            reg_a,
+            reg_b: 0,
+            reg_c: 0,
+        }
+    }
+
+    #[test]
+    fn part2_example() {
+        assert!(part2(&machine(2024), &[0, 3, 5, 4, 3, 0]).is_ok());
+    }
+
+    // The part 1 example outputs A>>1, A>>2, ... (mod 8), so for its output
+    // to start 0,1 bit 2 of A would have to be both clear and set.
+    #[test]
+    fn part2_not_a_quine() {
+        let (machine, program) = parse_input("d17-example1.txt").unwrap();
+        assert!(part2(&machine, &program).is_err());
+    }
+
+    #[test]
+    fn program_line() {
+        for line in [
+            "Program: 0,1,5,4,3,0",
+            "Program: 0,1,5,4,3,0,",
+            "Program: 0,1,5,4,3,0   ",
+            "Program: 0, 1, 5, 4, 3, 0,  ",
+        ] {
+            assert_eq!(parse_program(line).unwrap(), [0, 1, 5, 4, 3, 0], "{line:?}");
+        }
+
+        for (line, err) in [
+            ("Program: ", "Program is empty"),
+            ("Program: ,", "Program is empty"),
+            (
+                "Program: 0,8,3,0",
+                "Invalid program value \"8\" at position 1",
+            ),
+            (
+                "Program: 0,,3,0",
+                "Invalid program value \"\" at position 1",
+            ),
+            ("0,1,5,4,3,0", "Malformed program line \"0,1,5,4,3,0\""),
+        ] {
+            assert_eq!(
+                parse_program(line).unwrap_err().to_string(),
+                err,
+                "{line:?}"
+            );
+        }
+    }
+
+    // The first pass through the part 1 example's loop, a step at a time
+    #[test]
+    fn trace_example() {
+        let (mut machine, program) = parse_input("d17-example1.txt").unwrap();
+        let expected = [
+            "ip= 0 Adv 1 (combo = 1)  A=729 B=0 C=0 -> A=364 B=0 C=0",
+            "ip= 2 Out 4 (combo = 364) A=364 B=0 C=0 -> A=364 B=0 C=0",
+            "ip= 4 Jnz 0              A=364 B=0 C=0 -> A=364 B=0 C=0",
+            "ip= 0 Adv 1 (combo = 1)  A=364 B=0 C=0 -> A=182 B=0 C=0",
+        ];
+        for line in expected {
+            let before = machine.clone();
+            let ip = before.instruction_pointer;
+            machine.execute_bounded(&program, None, Some(1), false);
+            let instruction = Instruction::from(program[ip]);
+            assert_eq!(
+                machine.trace_step(&before, &instruction, program[ip + 1]),
+                line
+            );
+        }
+    }
+
+    // "out A; jnz 0" never halts while A is nonzero
+    #[test]
+    fn bounded_infinite_loop() {
+        let program = [5, 4, 3, 0];
+        let mut m = machine(13);
+        let out = m.execute_bounded(&program, Some(100), None, false);
+        assert_eq!(out, [5; 100]);
+
+        // and it carries on from where it stopped
+        let out = m.execute_bounded(&program, None, Some(10), false);
+        assert_eq!(out, [5; 5]);
+    }
+
+    // The part 2 solver leans on the shape of the puzzle program: output one
+    // value, shift A right by 3 and loop until A is zero.  For such a program
+    // there is one output per octal digit of A (log8(A), rounded up), and
+    // dropping the lowest octal digit of A drops the first output and leaves
+    // the rest unchanged.  Check both for random values of A across the full
+    // range the solver searches.
+    #[test]
+    fn puzzle_program_properties() {
+        let (original_machine, program) = parse_input("d17.txt").unwrap();
+        let run_with_a = |a: isize| {
+            let mut machine = original_machine.clone();
+            machine.reg_a = a;
+            machine.execute(&program)
+        };
+
+        let max_bits = 3 * program.len() as u32;
+        let mut rng = XorShift(2024);
+        for _ in 0..1000 {
+            // pick the number of bits first so small values of A get coverage too
+            let bits = 1 + rng.below(max_bits as usize) as u32;
+            let a = (rng.next_u64() >> (64 - bits)) as isize;
+
+            let out = run_with_a(a);
+            let octal_digits = (a.max(1).ilog2() / 3 + 1) as usize;
+            assert_eq!(out.len(), octal_digits, "A={a:o} (octal)");
+            if a >= 8 {
+                let shifted = run_with_a(a >> 3);
+                assert_eq!(shifted[..], out[1..], "A={a:o} (octal)");
+            }
+        }
+    }
+}
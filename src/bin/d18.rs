@@ -4,7 +4,7 @@ use std::{
     path::Path,
 };
 
-use aoc::input_lines;
+use aoc::{input_lines, Part};
 use clap::Parser;
 use colored::Colorize;
 
@@ -33,6 +33,13 @@ struct Position {
     y: usize,
 }
 
+// The puzzle's "x,y" format
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
 impl Ord for Position {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.y.cmp(&other.y).then(self.x.cmp(&other.x))
@@ -63,13 +70,41 @@ struct Cli {
     #[arg(short, long, default_value = "d18.txt")]
     input: String,
 
-    #[arg(short, long, default_value_t = 70)]
+    #[arg(short, long, value_enum, default_value_t)]
+    part: Part,
+
+    /// Width and height of a square memory space
+    #[arg(short, long, default_value_t = 71)]
     dimensions: usize,
 
+    /// Width of the memory space, if it isn't square
+    #[arg(long)]
+    width: Option<usize>,
+
+    /// Height of the memory space, if it isn't square
+    #[arg(long)]
+    height: Option<usize>,
+
     #[arg(short, long, default_value_t = 1024)]
     bytes: usize,
 }
 
+impl Cli {
+    // (width, height) of the memory space
+    fn size(&self) -> (usize, usize) {
+        (
+            self.width.unwrap_or(self.dimensions),
+            self.height.unwrap_or(self.dimensions),
+        )
+    }
+}
+
+fn empty_map(width: usize, height: usize) -> Vec<Vec<MapEntry>> {
+    (0..height)
+        .map(|_y| (0..width).map(|_x| MapEntry::Open).collect())
+        .collect()
+}
+
 const DELTAS: [(isize, isize); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -122,7 +157,7 @@ fn find_neighbors(map: &[Vec<MapEntry>], position: Position) -> Vec<Position> {
         .filter_map(move |(dx, dy)| {
             let nx = position.x.checked_add_signed(*dx)?;
             let ny = position.y.checked_add_signed(*dy)?;
-            if nx >= map.len() || ny >= map.len() {
+            if ny >= map.len() || nx >= map[ny].len() {
                 return None;
             }
             if !matches!(map[ny][nx], MapEntry::Open) {
@@ -134,12 +169,15 @@ fn find_neighbors(map: &[Vec<MapEntry>], position: Position) -> Vec<Position> {
         .collect()
 }
 
-fn solve_maze_using_astar(map: &[Vec<MapEntry>]) -> Option<VecDeque<Position>> {
+/// Find a shortest path from the top left to the bottom right corner,
+/// returning its cost along with the positions (goal first).
+fn solve_maze_using_astar(map: &[Vec<MapEntry>]) -> Option<(usize, VecDeque<Position>)> {
     let mut frontier = BinaryHeap::new();
     let start_node = Node::default();
     frontier.push(start_node);
+    // the exit is in the bottom right corner
     let goal = Position {
-        x: map.len() - 1,
+        x: map[0].len() - 1,
         y: map.len() - 1,
     };
     let mut visited: HashSet<Position> = HashSet::new();
@@ -156,7 +194,7 @@ fn solve_maze_using_astar(map: &[Vec<MapEntry>]) -> Option<VecDeque<Position>> {
                 path.push_back(cur_node.position);
                 next_node = cur_node.prev;
             }
-            return Some(path);
+            return Some((node.cost, path));
         }
 
         for neigh_position in find_neighbors(map, node.position) {
@@ -199,15 +237,14 @@ fn solve_maze_using_astar(map: &[Vec<MapEntry>]) -> Option<VecDeque<Position>> {
 
 fn print_map_with_path(map: &[Vec<MapEntry>], path: &VecDeque<Position>) {
     print!("  ");
-    for x in 0..map.len() {
+    for x in 0..map[0].len() {
         print!("{}", x % 10);
     }
     println!("");
 
     for y in 0..map.len() {
         print!("{} ", y % 10);
-        for x in 0..map.len() {
-            let entry = map[y][x];
+        for (x, &entry) in map[y].iter().enumerate() {
             let pos = Position { x, y };
             let in_path = path.contains(&pos);
             let s = match (in_path, entry) {
@@ -231,67 +268,148 @@ fn part2() -> anyhow::Result<()> {
     // Let's try doing a binary search over the maze set instead.
 
     let cli = Cli::parse();
-    let corruption = parse_input(cli.input)?;
-    let base_map: Vec<Vec<MapEntry>> = (0..cli.dimensions)
-        .map(|_y| (0..cli.dimensions).map(|_x| MapEntry::Open).collect())
-        .collect();
+    let corruption = parse_input(&cli.input)?;
+    let (width, height) = cli.size();
+    let base_map = empty_map(width, height);
+
+    let blocker = find_blocker(&base_map, &corruption, cli.bytes, true)?;
+
+    // The first `blocker` bytes leave the exit reachable, so the byte that
+    // cuts it off is the one at that index.
+    println!("Problem Index = {blocker}");
+    println!("Part 2: {}", corruption[blocker]);
 
-    let mut low = 1024;
+    Ok(())
+}
+
+// The map after the first `count` bytes have fallen
+fn corrupt(
+    base_map: &[Vec<MapEntry>],
+    corruption: &[Position],
+    count: usize,
+) -> Vec<Vec<MapEntry>> {
+    let mut map = base_map.to_vec();
+    for pos in corruption.iter().take(count) {
+        map[pos.y][pos.x] = MapEntry::Corrupted;
+    }
+    map
+}
+
+// Binary search for the index of the first byte that cuts off the exit,
+// starting from the knowledge that the first `passable` bytes do not.
+fn find_blocker(
+    base_map: &[Vec<MapEntry>],
+    corruption: &[Position],
+    passable: usize,
+    verbose: bool,
+) -> anyhow::Result<usize> {
+    let corrupt = |count: usize| corrupt(base_map, corruption, count);
+
+    let mut low = passable.min(corruption.len());
     let mut high = corruption.len();
+    if solve_maze_using_astar(&corrupt(low)).is_none() {
+        anyhow::bail!("The exit is already blocked after {low} bytes");
+    }
+    if solve_maze_using_astar(&corrupt(high)).is_some() {
+        anyhow::bail!("The exit is still reachable after all {high} bytes");
+    }
+
     while high - low > 1 {
-        println!("low={low}, high={high}");
+        if verbose {
+            println!("low={low}, high={high}");
+        }
 
         // select our candidate in the middle of the range
         let candidate = low + (high - low) / 2;
 
         // corrupt our map with that amount of corruption
-        let mut map = base_map.clone();
-        for pos in corruption.iter().take(candidate) {
-            map[pos.y][pos.x] = MapEntry::Corrupted;
-        }
+        let map = corrupt(candidate);
 
         // Now, see if a* can come up with a solution.
         let solvable = solve_maze_using_astar(&map);
-        if let Some(solution) = solvable {
-            print_map_with_path(&map, &solution);
-            println!("   Yep ({candidate}) in {}", solution.len() - 1);
+        if let Some((cost, solution)) = solvable {
+            if verbose {
+                print_map_with_path(&map, &solution);
+                println!("   Yep ({candidate}) in {cost}");
+            }
             low = candidate;
         } else {
-            println!("   Nope ({candidate})");
+            if verbose {
+                println!("   Nope ({candidate})");
+            }
             high = candidate;
         }
     }
 
-    // The index in corruption ends up being the lower bound with how the indexing
-    // workings out, etc.
-    println!("Problem Index = {low} - {:?}", corruption[low]);
-
-    Ok(())
+    Ok(low)
 }
 
 fn part1() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    let corruption = parse_input(cli.input)?;
-    let mut map: Vec<Vec<MapEntry>> = (0..cli.dimensions)
-        .map(|_y| (0..cli.dimensions).map(|_x| MapEntry::Open).collect())
-        .collect();
+    let corruption = parse_input(&cli.input)?;
+    let (width, height) = cli.size();
+    let mut map = empty_map(width, height);
 
     for pos in corruption.iter().take(cli.bytes) {
         map[pos.y][pos.x] = MapEntry::Corrupted;
     }
 
-    let path = solve_maze_using_astar(&map).expect("Expected Solution");
+    let (cost, path) = solve_maze_using_astar(&map).expect("Expected Solution");
 
     print_map_with_path(&map, &path);
 
-    // The cost is the path length - 1 (the # of moves)
-    println!("Cost: {}", path.len() - 1);
+    println!("Part 1: {cost}");
 
     Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
-    part1()?;
-    part2()?;
+    let part = Cli::parse().part;
+    if part.part1() {
+        part1()?;
+    }
+    if part.part2() {
+        part2()?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A memory space wider than it is tall, with a wall down column 5 that
+    // leaves only the bottom row open, so mixing up the width and height
+    // either runs off the map or heads for the wrong corner.
+    #[test]
+    fn wide_memory_space() {
+        let mut map = empty_map(10, 6);
+        for row in map.iter_mut().take(5) {
+            row[5] = MapEntry::Corrupted;
+        }
+
+        let (cost, path) = solve_maze_using_astar(&map).unwrap();
+        assert_eq!(cost, 9 + 5);
+        assert_eq!(path.front(), Some(&Position { x: 9, y: 5 }));
+        assert_eq!(path.back(), Some(&Position { x: 0, y: 0 }));
+        assert!(path.contains(&Position { x: 5, y: 5 }));
+        assert!(path.iter().all(|p| p.x < 10 && p.y < 6));
+    }
+
+    // The worked example: 12 bytes fallen on a 7x7 memory space
+    #[test]
+    fn example() {
+        let corruption = parse_input("d18-example1.txt").unwrap();
+        let base_map = empty_map(7, 7);
+        let (cost, path) = solve_maze_using_astar(&corrupt(&base_map, &corruption, 12)).unwrap();
+        assert_eq!(cost, 22);
+        assert_eq!(path.len(), cost + 1);
+
+        // the blocker's index is also the number of bytes that can fall with
+        // the exit still reachable; one more and it's cut off
+        let index = find_blocker(&base_map, &corruption, 12, false).unwrap();
+        assert!(solve_maze_using_astar(&corrupt(&base_map, &corruption, index)).is_some());
+        assert!(solve_maze_using_astar(&corrupt(&base_map, &corruption, index + 1)).is_none());
+        assert_eq!(corruption[index], Position { x: 6, y: 1 });
+    }
+}
@@ -1,15 +1,16 @@
-use std::{collections::HashMap, path::Path};
+use std::path::Path;
 
 use aoc::input_lines;
 use clap::Parser;
 
-#[derive(Debug, Clone)]
-struct Inputs {
+// The towels are read up front, but patterns are each handled on their own so
+// they are read lazily as they're needed.
+struct Inputs<I: Iterator<Item = String>> {
     towels: Vec<String>,
-    patterns: Vec<String>,
+    patterns: I,
 }
 
-fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Inputs> {
+fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Inputs<impl Iterator<Item = String>>> {
     let mut lines = input_lines(path)?;
     let towels: Vec<String> = lines
         .next()
@@ -18,26 +19,47 @@ fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Inputs> {
         .map(|t| t.trim().to_string())
         .collect();
     let _ = lines.next();
-    let patterns: Vec<String> = lines.into_iter().collect();
 
-    Ok(Inputs { towels, patterns })
+    Ok(Inputs {
+        towels,
+        patterns: lines,
+    })
 }
 
 #[derive(Parser)]
 struct Cli {
     #[arg(short, long, default_value = "d19.txt")]
     input: String,
+
+    /// Report cache hits/misses and the longest pattern across all patterns
+    #[arg(long)]
+    stats: bool,
 }
 
-type Cache<'a> = HashMap<&'a str, usize>;
+// Memo of the number of arrangements for the suffix of the pattern starting
+// at each index; indexed by position so there's no hashing of slices.
+type Cache = Vec<Option<usize>>;
+
+// Instrumentation for the matcher, accumulated across patterns.
+#[derive(Debug, Default)]
+struct Stats {
+    lookups: usize,
+    hits: usize,
+    misses: usize,
+    longest_pattern: usize,
+}
 
-fn patterns_possible<'a>(
-    pattern: &'a str,
-    sorted_towels: &'a [&'a str],
+fn patterns_possible(
+    full_pattern: &str,
+    start: usize,
+    sorted_towels: &[&str],
     depth: usize,
     verbose: bool,
-    cache: &mut Cache<'a>,
+    cache: &mut Cache,
+    mut stats: Option<&mut Stats>,
 ) -> usize {
+    let pattern = &full_pattern[start..];
+
     // base case, the remaining pattern is an available towel; if not
     // then we know that the longest matching subpattern would at most
     // be the length of the closest match (though might be none).
@@ -48,8 +70,17 @@ fn patterns_possible<'a>(
         println!("pattern: {pattern}");
     }
 
-    if let Some(cached) = cache.get(&pattern) {
-        return *cached;
+    if let Some(stats) = stats.as_deref_mut() {
+        stats.lookups += 1;
+    }
+    if let Some(cached) = cache[start] {
+        if let Some(stats) = stats.as_deref_mut() {
+            stats.hits += 1;
+        }
+        return cached;
+    }
+    if let Some(stats) = stats.as_deref_mut() {
+        stats.misses += 1;
     }
 
     let mut possible = 0;
@@ -85,20 +116,27 @@ fn patterns_possible<'a>(
         }
 
         // we matched a subpattern, if the rest works out we're home free!
-        let remaining_possible =
-            patterns_possible(remainder, sorted_towels, depth + 1, verbose, cache);
+        let remaining_possible = patterns_possible(
+            full_pattern,
+            start + pivot + 1,
+            sorted_towels,
+            depth + 1,
+            verbose,
+            cache,
+            stats.as_deref_mut(),
+        );
         if remaining_possible > 0 {
             possible += remaining_possible;
         }
     }
 
-    cache.insert(pattern, possible);
+    cache[start] = Some(possible);
     return possible;
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    let inputs = parse_input(cli.input)?;
+    let inputs = parse_input(&cli.input)?;
 
     // Brainstorming for Part 1:
     // - Work through the target pattern in pieces with an expanding frontier; from
@@ -117,13 +155,25 @@ fn main() -> anyhow::Result<()> {
     // pattern we're dealing with (at least for p1).
     let mut ok_patterns = 0;
     let mut patterns_count = 0;
+    let mut stats = cli.stats.then(Stats::default);
     let mut towels_sorted: Vec<&str> = inputs.towels.iter().map(|t| t.as_ref()).collect();
     towels_sorted.sort();
     println!("Sorted: {towels_sorted:?}\n\n");
-    for pattern in inputs.patterns.iter() {
+    for pattern in inputs.patterns {
         println!("Working on pattern: {pattern}");
-        let mut cache = Default::default();
-        let patterns = patterns_possible(pattern, &towels_sorted, 0, false, &mut cache);
+        let mut cache: Cache = vec![None; pattern.len() + 1];
+        if let Some(stats) = stats.as_mut() {
+            stats.longest_pattern = stats.longest_pattern.max(pattern.len());
+        }
+        let patterns = patterns_possible(
+            &pattern,
+            0,
+            &towels_sorted,
+            0,
+            false,
+            &mut cache,
+            stats.as_mut(),
+        );
         if patterns > 0 {
             ok_patterns += 1;
         }
@@ -131,11 +181,46 @@ fn main() -> anyhow::Result<()> {
     }
 
     println!("Note: 482106311433668 is too low");
-    println!(
-        "Passing Patterns: {ok_patterns} / {}",
-        inputs.patterns.len()
-    );
-    println!("Possible Patterns: {patterns_count}");
+    println!("Part 1: {ok_patterns}");
+    println!("Part 2: {patterns_count}");
+
+    if let Some(stats) = stats {
+        println!(
+            "Cache: {} lookups, {} hits, {} misses; longest pattern {}",
+            stats.lookups, stats.hits, stats.misses, stats.longest_pattern
+        );
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_stats() {
+        let inputs = parse_input("d19-example1.txt").unwrap();
+        let mut towels_sorted: Vec<&str> = inputs.towels.iter().map(|t| t.as_ref()).collect();
+        towels_sorted.sort();
+
+        let mut stats = Stats::default();
+        let mut patterns_count = 0;
+        for pattern in inputs.patterns {
+            let mut cache: Cache = vec![None; pattern.len() + 1];
+            patterns_count += patterns_possible(
+                &pattern,
+                0,
+                &towels_sorted,
+                0,
+                false,
+                &mut cache,
+                Some(&mut stats),
+            );
+        }
+
+        assert_eq!(patterns_count, 16);
+        assert!(stats.hits > 0);
+        assert_eq!(stats.hits + stats.misses, stats.lookups);
+    }
+}
@@ -4,19 +4,34 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use anyhow::Context;
+use clap::Parser;
+
 fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Vec<i32>>> {
     let input_path = PathBuf::from(".").join("inputs").join(path);
     let f = File::open(input_path)?;
-    let reader = BufReader::new(f);
-    let records = reader
-        .lines()
-        .filter_map(|l| Some(l.ok()?))
-        .map(|l| {
-            l.split_whitespace()
-                .filter_map(|chunk| chunk.parse::<i32>().ok()?.into())
-                .collect::<Vec<i32>>()
-        })
-        .collect::<Vec<Vec<i32>>>();
+    parse_reports(BufReader::new(f))
+}
+
+// One report per line, skipping blank lines and `#` comments so annotated
+// inputs still parse; anything else that isn't a level is an error rather
+// than a (trivially safe) short report.
+fn parse_reports<R: BufRead>(reader: R) -> anyhow::Result<Vec<Vec<i32>>> {
+    let mut records = Vec::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let report = line
+            .split_whitespace()
+            .map(|chunk| chunk.parse::<i32>())
+            .collect::<Result<Vec<i32>, _>>()
+            .with_context(|| format!("Line {}: {line:?} is not a report", idx + 1))?;
+        records.push(report);
+    }
     Ok(records)
 }
 
@@ -47,25 +62,19 @@ mod p1 {
         return true;
     }
 
-    fn is_report_safe(report: &[i32]) -> bool {
+    pub fn is_report_safe(report: &[i32]) -> bool {
         // The levels are either all increasing or all decreasing.
         // Any two adjacent levels differ by at least one and at most three.
-        if report[1] > report[0] {
-            is_report_safe_increasing(report)
-        } else {
-            is_report_safe_decreasing(report)
+        // Reports with fewer than two levels are trivially safe, and a
+        // report whose first two levels match is neither.
+        if report.len() < 2 {
+            return true;
+        }
+        match report[1].cmp(&report[0]) {
+            std::cmp::Ordering::Greater => is_report_safe_increasing(report),
+            std::cmp::Ordering::Less => is_report_safe_decreasing(report),
+            std::cmp::Ordering::Equal => false,
         }
-    }
-
-    pub fn part1() -> anyhow::Result<()> {
-        let records = super::parse_input("d2-p1.txt")?;
-        let safe_count = records
-            .into_iter()
-            .filter(|r| is_report_safe(r.as_slice()))
-            .count();
-
-        println!("Safe Count: {safe_count}");
-        Ok(())
     }
 }
 
@@ -93,7 +102,7 @@ mod p2 {
         return true; // no failure case found
     }
 
-    fn is_report_safe_fault_tolerant(report: &[i32]) -> bool {
+    pub fn is_report_safe_fault_tolerant(report: &[i32]) -> bool {
         // fuck it, we'll do it live; just try every permutation combination
         // of the report series starting with the base case and then the
         // ones with one element removed.
@@ -112,24 +121,91 @@ mod p2 {
 
         return false;
     }
+}
 
-    pub fn part2() -> anyhow::Result<()> {
-        let reports = super::parse_input("d2-p1.txt")?;
-        let mut pass = 0;
-        for report in reports {
-            let res = is_report_safe_fault_tolerant(&report);
-            if res {
-                pass += 1;
-            }
+// Count the safe reports, both strictly (part 1) and with the problem
+// dampener tolerating a single bad level (part 2), in one pass.
+fn solve(reports: &[Vec<i32>]) -> (usize, usize) {
+    let mut safe = 0;
+    let mut dampened_safe = 0;
+    for report in reports {
+        if p1::is_report_safe(report) {
+            safe += 1;
+            dampened_safe += 1;
+        } else if p2::is_report_safe_fault_tolerant(report) {
+            dampened_safe += 1;
         }
-
-        println!("Safe: {pass}");
-        Ok(())
     }
+    (safe, dampened_safe)
+}
+
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d2-p1.txt")]
+    input: String,
 }
 
 fn main() -> anyhow::Result<()> {
-    p1::part1()?;
-    p2::part2()?;
+    let cli = Cli::parse();
+    let reports = parse_input(&cli.input)?;
+    let (safe, dampened_safe) = solve(&reports);
+    println!("Part 1: {safe}");
+    println!("Part 2: {dampened_safe}");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_REPORTS: [&[i32]; 6] = [
+        &[7, 6, 4, 2, 1],
+        &[1, 2, 7, 8, 9],
+        &[9, 7, 6, 2, 1],
+        &[1, 3, 2, 4, 5],
+        &[8, 6, 4, 4, 1],
+        &[1, 3, 6, 7, 9],
+    ];
+    const EXAMPLE_SAFE: (usize, usize) = (2, 4);
+
+    // Short and flat-starting reports, with whether each is safe strictly and
+    // with the dampener; `[5, 5, 6]` is only safe once a 5 is dropped.
+    const EDGE_REPORTS: [(&[i32], bool, bool); 4] = [
+        (&[], true, true),
+        (&[5], true, true),
+        (&[5, 5], false, true),
+        (&[5, 5, 6], false, true),
+    ];
+
+    #[test]
+    fn example() {
+        let reports: Vec<Vec<i32>> = EXAMPLE_REPORTS.iter().map(|r| r.to_vec()).collect();
+        assert_eq!(solve(&reports), EXAMPLE_SAFE);
+    }
+
+    #[test]
+    fn edge_reports() {
+        for (report, safe, dampened) in EDGE_REPORTS {
+            assert_eq!(p1::is_report_safe(report), safe, "{report:?}");
+            assert_eq!(
+                p2::is_report_safe_fault_tolerant(report),
+                dampened,
+                "{report:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn blank_and_comment_lines_are_skipped() {
+        let input = "# example reports\n7 6 4 2 1\n\n1 2 7 8 9\n  # unsafe\n9 7 6 2 1\n\n";
+        let reports = parse_reports(input.as_bytes()).unwrap();
+        assert_eq!(reports, EXAMPLE_REPORTS[..3]);
+        assert_eq!(solve(&reports), (1, 1));
+    }
+
+    #[test]
+    fn line_without_levels_is_an_error() {
+        let err = parse_reports("7 6 4 2 1\nsafe?\n".as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "Line 2: \"safe?\" is not a report");
+    }
+}
@@ -1,12 +1,13 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, VecDeque},
     fmt::Display,
     path::Path,
 };
 
-use aoc::input_lines;
+use aoc::{cli::CommonArgs, input_lines};
 use clap::Parser;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy)]
 enum MapEntry {
@@ -54,10 +55,14 @@ struct Map {
 }
 
 fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Map> {
+    Ok(parse_map(input_lines(path)?))
+}
+
+fn parse_map<I: Iterator<Item = String>>(lines: I) -> Map {
     let mut entries: Vec<Vec<MapEntry>> = Default::default();
     let mut start = Position { x: 0, y: 0 };
     let mut end = Position { x: 0, y: 0 };
-    for (y, line) in input_lines(path)?.into_iter().enumerate() {
+    for (y, line) in lines.enumerate() {
         entries.push(Vec::new());
         for (x, c) in line.chars().enumerate() {
             let entry = match c {
@@ -79,12 +84,11 @@ fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Map> {
         }
     }
 
-    let map = Map {
+    Map {
         entries,
         start,
         end,
-    };
-    Ok(map)
+    }
 }
 
 fn print_map(map: &Map) {
@@ -98,14 +102,27 @@ fn print_map(map: &Map) {
 
 #[derive(Parser)]
 struct Cli {
-    #[arg(short, long)]
-    input: String,
+    #[command(flatten)]
+    common: CommonArgs,
 
+    /// Minimum savings for a cheat to count towards the answer
     #[arg(short, long, default_value_t = 100)]
     threshold_picoseconds: usize,
 
-    #[arg(short, long, default_value_t = 2)]
-    cheat_duration: usize,
+    /// Minimum savings for a cheat to be displayed in the histogram (defaults
+    /// to the counting threshold)
+    #[arg(long)]
+    histogram_min_picoseconds: Option<usize>,
+
+    /// Run once with this cheat duration rather than the 2 (part 1) and 20
+    /// (part 2) picoseconds from the puzzle
+    #[arg(short, long)]
+    cheat_duration: Option<usize>,
+
+    /// Print each run as a line of JSON with the histogram and total rather
+    /// than the histogram and answer
+    #[arg(long)]
+    json: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -119,6 +136,141 @@ fn manhattan_distance(p1: &Position, p2: &Position) -> usize {
     p1.x.abs_diff(p2.x) + p1.y.abs_diff(p2.y)
 }
 
+/// Steps needed to reach each piece of road from some fixed position (or to
+/// reach it from each piece of road); road that can't be reached at all has
+/// no entry.
+type RoadDistances = HashMap<Position, usize>;
+
+/// Everything about the track that cheats are measured against.
+#[derive(Debug)]
+struct Track {
+    /// Cost to reach each piece of road from the start
+    from_start: RoadDistances,
+    /// Cost to reach the end from each piece of road
+    to_end: RoadDistances,
+    /// Time taken to race from the start to the end without cheating
+    race: usize,
+}
+
+// Distances to and from every piece of road connected to the track, dead
+// ends included, or None if the start isn't connected to the end.  A cheat
+// is timed as the race to where it starts, the cheat itself and the race on
+// from where it ends, so road that is off the optimal route (dead ends) is
+// only used where that actually comes out ahead, and pockets of road that
+// are cut off from the track never take part.  This only needs doing once
+// per map no matter how many cheat durations are tried.
+fn prepare(map: &Map) -> Option<Track> {
+    let from_start = distances_from(map, map.start);
+    let race = *from_start.get(&map.end)?;
+    Some(Track {
+        from_start,
+        to_end: distances_from(map, map.end),
+        race,
+    })
+}
+
+// Number of cheats of up to `cheat_duration` picoseconds, keyed by how much
+// time they save, for every cheat saving at least `min_savings`.
+fn savings_histogram(
+    track: &Track,
+    cheat_duration: usize,
+    min_savings: usize,
+) -> HashMap<usize, usize> {
+    let min_savings = min_savings.max(1);
+    let mut shortcuts: Vec<(Cheat, usize)> = Vec::new();
+    for (position, before) in track.from_start.iter() {
+        for (tpos, after) in track.to_end.iter() {
+            let dist = manhattan_distance(position, tpos);
+            let time = before + dist + after;
+            if dist <= cheat_duration && time + min_savings <= track.race {
+                let savings = track.race - time;
+                let cheat = Cheat {
+                    start: position.clone(),
+                    end: tpos.clone(),
+                };
+                shortcuts.push((cheat, savings))
+            }
+        }
+    }
+
+    let mut shortcuts_by_savings: HashMap<usize, usize> = HashMap::new();
+    for (_cheat, cost) in shortcuts {
+        let entry = shortcuts_by_savings.entry(cost).or_default();
+        *entry += 1;
+    }
+    shortcuts_by_savings
+}
+
+/// A single run as printed by `--json`; the histogram is keyed by the
+/// picoseconds saved, and `total` counts the cheats saving at least
+/// `threshold`.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct HistogramReport {
+    cheat_duration: usize,
+    threshold: usize,
+    histogram: BTreeMap<usize, usize>,
+    total: usize,
+}
+
+impl HistogramReport {
+    // Only the savings of at least `histogram_min` go in the report's
+    // histogram, but the total is counted from all of `shortcuts_by_savings`.
+    fn new(
+        cheat_duration: usize,
+        threshold: usize,
+        histogram_min: usize,
+        shortcuts_by_savings: &HashMap<usize, usize>,
+    ) -> Self {
+        HistogramReport {
+            cheat_duration,
+            threshold,
+            histogram: shortcuts_by_savings
+                .iter()
+                .filter(|(savings, _count)| **savings >= histogram_min)
+                .map(|(savings, count)| (*savings, *count))
+                .collect(),
+            total: count_cheats(shortcuts_by_savings, threshold),
+        }
+    }
+}
+
+// The number of cheats in a histogram from `savings_histogram` that save at
+// least `threshold` picoseconds; the histogram needs to go at least that low.
+fn count_cheats(shortcuts_by_savings: &HashMap<usize, usize>, threshold: usize) -> usize {
+    shortcuts_by_savings
+        .iter()
+        .filter(|(savings, _count)| **savings >= threshold)
+        .map(|(_savings, count)| count)
+        .sum()
+}
+
+// Steps needed to reach every piece of road from `from` without cheating.
+fn distances_from(map: &Map, from: Position) -> RoadDistances {
+    let mut distances = HashMap::from([(from, 0)]);
+    let mut frontier = VecDeque::from([from]);
+    while let Some(position) = frontier.pop_front() {
+        let distance = distances[&position];
+        for (dx, dy) in DELTAS {
+            let (Some(x), Some(y)) = (
+                position.x.checked_add_signed(dx),
+                position.y.checked_add_signed(dy),
+            ) else {
+                continue;
+            };
+            let pos = Position { x, y };
+            match map.entries.get(y).and_then(|row| row.get(x)) {
+                Some(MapEntry::Wall) | None => {}
+                Some(_) if !distances.contains_key(&pos) => {
+                    distances.insert(pos, distance + 1);
+                    frontier.push_back(pos);
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    distances
+}
+
 fn solve() -> anyhow::Result<()> {
     // Part 1 Reasoning:
     //
@@ -142,82 +294,258 @@ fn solve() -> anyhow::Result<()> {
     // that point.  Then, for each point, see if there is another piece of
     // road with a manhattan distance of 2 away that has a lower cost; that
     // difference is the picoseconds saved.
+    //
+    // Part 2 is the same with cheats lasting up to 20 picoseconds.
     let cli = Cli::parse();
-    let map = parse_input(cli.input)?;
-    print_map(&map);
-
-    // walk the map from the end back to the start with the step
-    // along the way being the cost (which we record)
-    let mut visited: HashSet<Position> = HashSet::new();
-    let mut road_costs: HashMap<Position, usize> = HashMap::new();
-    let mut next_position = Some(map.end);
-    let mut cost = 0;
-    while let Some(position) = next_position {
-        visited.insert(position);
-        road_costs.insert(position, cost);
-        if position == map.start {
-            break;
-        }
+    let map = parse_input(cli.common.input_or("d20.txt"))?;
+    if cli.common.verbose {
+        print_map(&map);
+    }
 
-        next_position = DELTAS
+    let Some(track) = prepare(&map) else {
+        anyhow::bail!("The start is not connected to the end");
+    };
+    let threshold = cli.threshold_picoseconds;
+    let histogram_min = cli.histogram_min_picoseconds.unwrap_or(threshold);
+
+    let runs: Vec<(Option<usize>, usize)> = match cli.cheat_duration {
+        Some(cheat_duration) => vec![(None, cheat_duration)],
+        None => [(1, 2), (2, 20)]
             .into_iter()
-            .filter_map(|(dx, dy)| {
-                let x = position.x.checked_add_signed(dx)?;
-                let y = position.y.checked_add_signed(dy)?;
-                let pos = Position { x, y };
-                let entry = map.entries[y][x];
-                if visited.contains(&pos) || !matches!(entry, MapEntry::Road | MapEntry::Start) {
-                    return None;
-                }
-                Some(pos)
+            .filter(|(part, _)| match part {
+                1 => cli.common.part.part1(),
+                _ => cli.common.part.part2(),
             })
-            .nth(0);
-        cost += 1;
-    }
+            .map(|(part, cheat_duration)| (Some(part), cheat_duration))
+            .collect(),
+    };
 
-    let mut shortcuts: Vec<(Cheat, usize)> = Vec::new();
-    for (position, cost) in road_costs.iter() {
-        for (tpos, tcost) in road_costs.iter() {
-            let dist = manhattan_distance(position, tpos);
-            if dist <= cli.cheat_duration
-                && tcost < cost
-                && cost - tcost - dist >= cli.threshold_picoseconds
-            {
-                let savings = cost - tcost - dist;
-                let cheat = Cheat {
-                    start: position.clone(),
-                    end: tpos.clone(),
-                };
-                shortcuts.push((cheat, savings))
+    for (part, cheat_duration) in runs {
+        // the answer is counted from the same histogram that's shown, so it
+        // has to go down to the lower of the two floors
+        let label = match part {
+            Some(part) => format!("Part {part}"),
+            None => format!("Cheats of up to {cheat_duration}"),
+        };
+        let histogram = cli.common.timed(&label, || {
+            savings_histogram(&track, cheat_duration, histogram_min.min(threshold))
+        });
+        if cli.json {
+            let report = HistogramReport::new(cheat_duration, threshold, histogram_min, &histogram);
+            println!("{}", serde_json::to_string(&report)?);
+            continue;
+        }
+
+        for (savings, solutions) in histogram.iter().sorted() {
+            if *savings >= histogram_min {
+                println!("{savings}: {solutions}");
             }
         }
+        let cheats = count_cheats(&histogram, threshold);
+        match part {
+            Some(part) => println!("Part {part}: {cheats}"),
+            None => println!(
+                "Cheats (duration <= {cheat_duration}) saving >= {threshold} picoseconds = {cheats}"
+            ),
+        }
     }
 
-    let mut shortcuts_by_savings: HashMap<usize, usize> = HashMap::new();
-    for (_cheat, cost) in shortcuts {
-        let entry = shortcuts_by_savings.entry(cost).or_default();
-        *entry += 1;
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    solve()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A track with a pocket of road sealed off below it, close enough that
+    // cheats would reach it if it had distances.
+    const POCKET_MAP: &str = "\
+###########
+#S#...#...#
+#.#.#.#.#.#
+#...#...#E#
+###########
+##...######
+###########";
+
+    #[test]
+    fn pocket_is_left_out() {
+        let map = parse_map(POCKET_MAP.lines().map(String::from));
+        let track = prepare(&map).unwrap();
+        for x in 2..=4 {
+            assert!(!track.to_end.contains_key(&Position { x, y: 5 }));
+        }
+
+        let walled = parse_map(
+            POCKET_MAP
+                .replace("##...##", "#######")
+                .lines()
+                .map(String::from),
+        );
+        let walled_track = prepare(&walled).unwrap();
+        for cheat_duration in [2, 20] {
+            assert_eq!(
+                savings_histogram(&track, cheat_duration, 1),
+                savings_histogram(&walled_track, cheat_duration, 1),
+                "cheats of up to {cheat_duration}"
+            );
+        }
     }
 
-    for (savings, solutions) in shortcuts_by_savings.iter().sorted() {
-        println!("{savings}: {solutions}");
+    // Every cheat of up to 2 picoseconds in the example saves something;
+    // there are 44 of them.
+    #[test]
+    fn json_report_round_trips() {
+        let track = prepare(&parse_input("d20-example1.txt").unwrap()).unwrap();
+        let histogram = savings_histogram(&track, 2, 1);
+        let report = HistogramReport::new(2, 1, 1, &histogram);
+        let json = serde_json::to_string(&report).unwrap();
+        let parsed: HistogramReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, report);
+        assert_eq!(parsed.total, 44);
+        assert_eq!(parsed.histogram.values().sum::<usize>(), 44);
     }
 
-    let cheats_saving_gt_treshold: usize = shortcuts_by_savings
-        .iter()
-        .filter(|(savings, _count)| **savings >= cli.threshold_picoseconds)
-        .map(|(_savings, count)| *count)
-        .sum();
+    // The puzzle's breakdown of the 2 picosecond cheats in the example, by
+    // picoseconds saved
+    const EXAMPLE_HISTOGRAM: [(usize, usize); 11] = [
+        (2, 14),
+        (4, 14),
+        (6, 2),
+        (8, 4),
+        (10, 2),
+        (12, 3),
+        (20, 1),
+        (36, 1),
+        (38, 1),
+        (40, 1),
+        (64, 1),
+    ];
 
-    println!(
-        "Cheats (duration <= {}) saving >= {} picoseconds = {cheats_saving_gt_treshold}",
-        cli.cheat_duration, cli.threshold_picoseconds
-    );
+    // The histogram keeps everything down to 2ps, while the total only counts
+    // the cheats saving at least 20ps.
+    #[test]
+    fn histogram_below_threshold() {
+        let track = prepare(&parse_input("d20-example1.txt").unwrap()).unwrap();
+        let histogram = savings_histogram(&track, 2, 2);
+        let report = HistogramReport::new(2, 20, 2, &histogram);
+        assert_eq!(report.histogram, BTreeMap::from(EXAMPLE_HISTOGRAM));
+        assert_eq!(report.total, 5);
+    }
 
-    Ok(())
-}
+    // A track is only read once prepared, so it can be reused for any number
+    // of runs in any order.
+    #[test]
+    fn track_reuse() {
+        let map = parse_input("d20-example1.txt").unwrap();
+        let track = prepare(&map).unwrap();
+        for cheat_duration in [2, 20, 2] {
+            assert_eq!(
+                savings_histogram(&track, cheat_duration, 1),
+                savings_histogram(&prepare(&map).unwrap(), cheat_duration, 1),
+                "cheats of up to {cheat_duration}"
+            );
+        }
+        assert_eq!(
+            savings_histogram(&track, 2, 1),
+            HashMap::from(EXAMPLE_HISTOGRAM)
+        );
+    }
 
-fn main() -> anyhow::Result<()> {
-    solve()?;
-    Ok(())
+    // The cheats straight from the puzzle's definition, to check the
+    // histogram against: for every pair of road positions close enough together, race
+    // from the start to the first, cheat to the second and race on to the end,
+    // and compare that against the time taken without cheating.  Slow, as every
+    // pair on the map is looked at.
+    fn count_cheats_bruteforce(map: &Map, cheat_duration: usize, threshold: usize) -> usize {
+        let threshold = threshold.max(1);
+        let from_start = distances_from(map, map.start);
+        let to_end = distances_from(map, map.end);
+        let Some(&race) = from_start.get(&map.end) else {
+            return 0;
+        };
+
+        let roads: Vec<Position> = map
+            .entries
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, entry)| !matches!(entry, MapEntry::Wall))
+                    .map(move |(x, _)| Position { x, y })
+            })
+            .collect();
+        let mut count = 0;
+        for cheat_start in &roads {
+            for cheat_end in &roads {
+                let dist = manhattan_distance(cheat_start, cheat_end);
+                if dist > cheat_duration {
+                    continue;
+                }
+                let (Some(before), Some(after)) =
+                    (from_start.get(cheat_start), to_end.get(cheat_end))
+                else {
+                    continue;
+                };
+                if before + dist + after + threshold <= race {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    // Cheats in the example worth checking by brute force, as (duration,
+    // threshold, count); the counts are from the puzzle description.
+    const BRUTEFORCE_CASES: [(usize, usize, usize); 4] =
+        [(2, 1, 44), (2, 20, 5), (20, 50, 285), (20, 76, 3)];
+
+    // A straight track from S to E with a dead end branching off below it.  No
+    // cheat can save anything, but cheating from the far end of the dead end
+    // back up to the track looks like it does when measured only by the
+    // distance left to the end.
+    const DEAD_END_MAP: &str = "\
+#################
+#S.............E#
+#######.#########
+#............####
+#################";
+
+    #[test]
+    fn bruteforce_example() {
+        let map = parse_input("d20-example1.txt").unwrap();
+        let track = prepare(&map).unwrap();
+        for (cheat_duration, threshold, expected) in BRUTEFORCE_CASES {
+            let histogram = savings_histogram(&track, cheat_duration, threshold);
+            let counts = (
+                count_cheats(&histogram, threshold),
+                count_cheats_bruteforce(&map, cheat_duration, threshold),
+            );
+            assert_eq!(
+                counts,
+                (expected, expected),
+                "cheats of up to {cheat_duration} saving >= {threshold}"
+            );
+        }
+    }
+
+    #[test]
+    fn bruteforce_dead_end() {
+        let map = parse_map(DEAD_END_MAP.lines().map(String::from));
+        let track = prepare(&map).unwrap();
+        for cheat_duration in [2, 20] {
+            let histogram = savings_histogram(&track, cheat_duration, 1);
+            let counts = (
+                count_cheats(&histogram, 1),
+                count_cheats_bruteforce(&map, cheat_duration, 1),
+            );
+            assert_eq!(counts, (0, 0), "cheats of up to {cheat_duration}");
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use std::{cell::LazyCell, collections::HashMap, path::Path};
 
-use aoc::input_lines;
+use aoc::{input_lines, Part};
 use clap::Parser;
 use itertools::Itertools;
 
@@ -93,7 +93,7 @@ fn key_cost(cache: &Cache, robot_depth: usize, key_start: char, key_end: char) -
     }
 }
 
-fn keypresses_cost(cache: &mut Cache, robot_depth: usize, key_seq: &str) -> usize {
+fn keypresses_cost(cache: &Cache, robot_depth: usize, key_seq: &str) -> usize {
     format!("A{key_seq}")
         .chars()
         .tuple_windows()
@@ -101,6 +101,54 @@ fn keypresses_cost(cache: &mut Cache, robot_depth: usize, key_seq: &str) -> usiz
         .sum()
 }
 
+// The key sequences worth trying to move from `start_pos` to `end_pos` on
+// `keypad` and press the key there: going all the way horizontally first or
+// all the way vertically first, so long as the corner turned isn't the blank.
+fn candidate_sequences(
+    keypad: &HashMap<char, Position>,
+    start_pos: &Position,
+    end_pos: &Position,
+) -> Vec<String> {
+    let delta = start_pos.delta(&end_pos);
+    let horizontal_dist = delta.dx.abs() as usize;
+    let vertical_dist = delta.dy.abs() as usize;
+
+    let horizontal_keys = if end_pos.x > start_pos.x { ">" } else { "<" }.repeat(horizontal_dist);
+    let vertical_keys = if end_pos.y < start_pos.y { "^" } else { "v" }.repeat(vertical_dist);
+
+    let horizontal_key_seq = format!("{horizontal_keys}{vertical_keys}A");
+    let vertical_key_seq = format!("{vertical_keys}{horizontal_keys}A");
+
+    let Position { x: x_blank, y: y_blank } = keypad[&' '];
+    let mut candidates = Vec::new();
+    if (end_pos.x, start_pos.y) != (x_blank, y_blank) {
+        candidates.push(horizontal_key_seq);
+    }
+    if (start_pos.x, end_pos.y) != (x_blank, y_blank) {
+        candidates.push(vertical_key_seq);
+    }
+    candidates
+}
+
+// Fewest presses to move from `start_pos` to `end_pos` on `keypad` and press
+// the key there, given the cost of typing a key sequence on the keypad one
+// robot further down.
+fn move_cost<F>(
+    keypad: &HashMap<char, Position>,
+    start_pos: &Position,
+    end_pos: &Position,
+    mut sequence_cost: F,
+) -> usize
+where
+    F: FnMut(&str) -> usize,
+{
+    candidate_sequences(keypad, start_pos, end_pos)
+        .iter()
+        .map(|key_seq| sequence_cost(key_seq))
+        .min()
+        .unwrap_or(usize::MAX)
+}
+
 fn populate_cache_for_robot(
     cache: &mut Cache,
     robot_depth: usize,
@@ -108,33 +156,10 @@ fn populate_cache_for_robot(
 ) {
     for (&start_key, start_pos) in keypad.iter() {
         for (&end_key, end_pos) in keypad.iter() {
-            let delta = start_pos.delta(&end_pos);
-            let horizontal_dist = delta.dx.abs() as usize;
-            let vertical_dist = delta.dy.abs() as usize;
-
-            let horizontal_keys = if end_pos.x > start_pos.x { ">" } else { "<" }.repeat(horizontal_dist);
-            let vertical_keys = if end_pos.y < start_pos.y { "^" } else { "v" }.repeat(vertical_dist);
-
-            let horizontal_key_seq = format!("{horizontal_keys}{vertical_keys}A");
-            let vertical_key_seq = format!("{vertical_keys}{horizontal_keys}A");
-
-            let Position { x: x_blank, y: y_blank } = keypad[&' '];
-            let min_horizontal = if (end_pos.x, start_pos.y) != (x_blank, y_blank) {
-                keypresses_cost(cache, robot_depth - 1, &horizontal_key_seq)
-            } else {
-                usize::MAX
-            };
-
-            let min_vertical = if (start_pos.x, end_pos.y) != (x_blank, y_blank) {
-                keypresses_cost(cache, robot_depth - 1, &vertical_key_seq)
-            } else {
-                usize::MAX
-            };
-
-            cache.insert(
-                (robot_depth, start_key, end_key),
-                min_horizontal.min(min_vertical),
-            );
+            let cost = move_cost(keypad, start_pos, end_pos, |key_seq| {
+                keypresses_cost(cache, robot_depth - 1, key_seq)
+            });
+            cache.insert((robot_depth, start_key, end_key), cost);
         }
     }
 }
@@ -153,30 +178,100 @@ fn build_cache(num_robots: usize) -> Cache {
     cache
 }
 
-fn compute_complexity(presses: usize, code: &[char]) -> usize {
+// Like `key_cost`, but rather than needing every transition at every depth
+// worked out up front by `build_cache`, only the ones needed are worked out
+// (recursively) as they come up and remembered in `memo`.  The numeric keypad
+// is at depth `num_robots + 1`, so a memo is only good for one robot count.
+fn lazy_key_cost(
+    memo: &mut Cache,
+    num_robots: usize,
+    robot_depth: usize,
+    key_start: char,
+    key_end: char,
+) -> usize {
+    if robot_depth == 0 {
+        return 1;
+    }
+    if let Some(&cost) = memo.get(&(robot_depth, key_start, key_end)) {
+        return cost;
+    }
+
+    let keypad = if robot_depth == num_robots + 1 {
+        NUMBER_PAD
+    } else {
+        DIRECTIONAL_PAD
+    };
+    let cost = move_cost(&keypad, &keypad[&key_start], &keypad[&key_end], |key_seq| {
+        lazy_keypresses_cost(memo, num_robots, robot_depth - 1, key_seq)
+    });
+    memo.insert((robot_depth, key_start, key_end), cost);
+    cost
+}
+
+fn lazy_keypresses_cost(
+    memo: &mut Cache,
+    num_robots: usize,
+    robot_depth: usize,
+    key_seq: &str,
+) -> usize {
+    format!("A{key_seq}")
+        .chars()
+        .tuple_windows()
+        .map(|(key_start, key_end)| {
+            lazy_key_cost(memo, num_robots, robot_depth, key_start, key_end)
+        })
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Complexity {
+    presses: usize,
+    numeric: usize,
+}
+
+impl Complexity {
+    fn value(&self) -> usize {
+        self.presses * self.numeric
+    }
+}
+
+fn compute_complexity(presses: usize, code: &[char]) -> Complexity {
     let digits: String = code.iter().filter(|c| c.is_digit(10)).collect();
-    let num_value: usize = digits.parse().expect("Failed to parse as numeric value");
-    println!(
-        "{} => {} * {num_value}",
-        code.iter().collect::<String>(),
-        presses
-    );
-    return num_value * presses;
+    let numeric: usize = digits.parse().expect("Failed to parse as numeric value");
+    Complexity { presses, numeric }
+}
+
+// The cache only depends on the number of robots, so it can be built once
+// with `build_cache` and shared across codes.
+pub fn solve_code_for_keypresses(cache: &Cache, code: &[char], num_robots: usize) -> usize {
+    let chars: String = code.iter().collect();
+    keypresses_cost(cache, num_robots + 1, &chars)
 }
 
-fn solve_code_for_keypresses(code: &[char], num_robots: usize) -> usize {
+// Same as `solve_code_for_keypresses` using (and adding to) a lazily filled
+// memo rather than a prebuilt cache.
+pub fn solve_code_lazily(memo: &mut Cache, code: &[char], num_robots: usize) -> usize {
     let chars: String = code.iter().collect();
-    let mut cache = build_cache(num_robots);
-    keypresses_cost(&mut cache, num_robots + 1, &chars)
+    lazy_keypresses_cost(memo, num_robots, num_robots + 1, &chars)
 }
 
 #[derive(Parser)]
 struct Cli {
-    #[arg(short, long)]
+    #[arg(short, long, default_value = "d21.txt")]
     input: String,
 
+    #[arg(short, long, value_enum, default_value_t)]
+    part: Part,
+
+    /// Run once with this many directional robots rather than the 2 (part 1)
+    /// and 25 (part 2) from the puzzle
     #[arg(short, long)]
-    robots: usize,
+    robots: Option<usize>,
+
+    /// Work out key costs as they're needed rather than building the cache
+    /// for every robot up front
+    #[arg(long)]
+    lazy: bool,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -189,16 +284,166 @@ fn main() -> anyhow::Result<()> {
     // there on my own but the brain was moving a bit slow.
 
     let cli = Cli::parse();
-    let final_codes = parse_input(cli.input)?;
-    let mut sum: usize = 0;
-    for code in final_codes {
-        let presses = solve_code_for_keypresses(&code, cli.robots);
-        println!("{}: {presses}", code.iter().collect::<String>());
-        let complexity = compute_complexity(presses, &code);
-        sum += complexity;
-    }
 
-    println!("Total Complexity: {sum}");
+    let final_codes = parse_input(&cli.input)?;
+    let runs: Vec<(Option<usize>, usize)> = match cli.robots {
+        Some(num_robots) => vec![(None, num_robots)],
+        None => [(1, 2), (2, 25)]
+            .into_iter()
+            .filter(|(part, _)| match part {
+                1 => cli.part.part1(),
+                _ => cli.part.part2(),
+            })
+            .map(|(part, num_robots)| (Some(part), num_robots))
+            .collect(),
+    };
+
+    for (part, num_robots) in runs {
+        let cache = if cli.lazy {
+            Cache::new()
+        } else {
+            build_cache(num_robots)
+        };
+        let mut memo = Cache::new();
+        let mut sum: usize = 0;
+        for code in &final_codes {
+            let presses = if cli.lazy {
+                solve_code_lazily(&mut memo, code, num_robots)
+            } else {
+                solve_code_for_keypresses(&cache, code, num_robots)
+            };
+            println!("{}: {presses}", code.iter().collect::<String>());
+            let complexity = compute_complexity(presses, code);
+            println!(
+                "{} => {} * {}",
+                code.iter().collect::<String>(),
+                complexity.presses,
+                complexity.numeric
+            );
+            sum += complexity.value();
+        }
+
+        match part {
+            Some(part) => println!("Part {part}: {sum}"),
+            None => println!("Total Complexity ({num_robots} robots): {sum}"),
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Worked examples from the puzzle text with two directional robots
+    // between us and the numeric keypad.
+    const EXAMPLE_ROBOTS: usize = 2;
+    const EXAMPLE_PRESSES: [(&str, usize); 5] = [
+        ("029A", 68),
+        ("980A", 60),
+        ("179A", 68),
+        ("456A", 64),
+        ("379A", 64),
+    ];
+
+    #[test]
+    fn examples() {
+        let cache = build_cache(EXAMPLE_ROBOTS);
+        let mut total = 0;
+        for (code, expected) in EXAMPLE_PRESSES {
+            let code: Vec<char> = code.chars().collect();
+            let presses = solve_code_for_keypresses(&cache, &code, EXAMPLE_ROBOTS);
+            assert_eq!(presses, expected, "{code:?}");
+            total += compute_complexity(presses, &code).value();
+        }
+        assert_eq!(total, 126384);
+    }
+
+    // the lazy memo should come to the same totals as the prebuilt cache
+    #[test]
+    fn lazy_matches_cache() {
+        for num_robots in [EXAMPLE_ROBOTS, 25] {
+            let cache = build_cache(num_robots);
+            let mut memo = Cache::new();
+            for (code, _) in EXAMPLE_PRESSES {
+                let code: Vec<char> = code.chars().collect();
+                assert_eq!(
+                    solve_code_lazily(&mut memo, &code, num_robots),
+                    solve_code_for_keypresses(&cache, &code, num_robots),
+                    "{code:?} with {num_robots} robots"
+                );
+            }
+        }
+    }
+
+    // Follow the arrows in `key_seq` from `start` on `keypad`, returning where
+    // the arm ends up, or None if it's ever over the blank or off the keypad.
+    fn replay(
+        keypad: &HashMap<char, Position>,
+        start: &Position,
+        key_seq: &str,
+    ) -> Option<Position> {
+        let blank = keypad[&' '];
+        let mut pos = *start;
+        for key in key_seq.chars() {
+            let (dx, dy) = match key {
+                '^' => (0, -1),
+                'v' => (0, 1),
+                '<' => (-1, 0),
+                '>' => (1, 0),
+                _ => continue, // pressing A doesn't move the arm
+            };
+            pos = Position {
+                x: pos.x.checked_add_signed(dx)?,
+                y: pos.y.checked_add_signed(dy)?,
+            };
+            if pos == blank || !keypad.values().any(|p| *p == pos) {
+                return None;
+            }
+        }
+        Some(pos)
+    }
+
+    // Whether typing `key_seq` starting over `start` keeps the arm off the blank
+    // the whole way and leaves it over `end`.
+    fn path_avoids_blank(
+        keypad: &HashMap<char, Position>,
+        start: &Position,
+        end: &Position,
+        key_seq: &str,
+    ) -> bool {
+        replay(keypad, start, key_seq) == Some(*end)
+    }
+
+    // Every sequence tried between every pair of keys on both keypads has to
+    // steer clear of the blank, and there must always be at least one.
+    #[test]
+    fn candidates_avoid_blank() {
+        for (name, keypad) in [("numeric", NUMBER_PAD), ("directional", DIRECTIONAL_PAD)] {
+            let keys: Vec<(char, Position)> = keypad
+                .iter()
+                .filter(|(key, _)| **key != ' ')
+                .map(|(key, pos)| (*key, *pos))
+                .collect();
+            for ((start_key, start), (end_key, end)) in keys.iter().cartesian_product(&keys) {
+                let candidates = candidate_sequences(&keypad, start, end);
+                assert!(
+                    !candidates.is_empty(),
+                    "{start_key} to {end_key} on the {name} keypad"
+                );
+                for key_seq in candidates {
+                    assert!(
+                        path_avoids_blank(&keypad, start, end, &key_seq),
+                        "{key_seq} from {start_key} to {end_key} on the {name} keypad"
+                    );
+                }
+            }
+        }
+
+        // and the check itself has to catch a sequence that does cross it
+        let keypad = NUMBER_PAD;
+        let (a, one) = (keypad[&'A'], keypad[&'1']);
+        assert!(!path_avoids_blank(&keypad, &a, &one, "<<^A"));
+    }
+}
@@ -1,6 +1,6 @@
-use std::{path::Path};
+use std::{collections::HashMap, path::Path};
 
-use aoc::input_lines;
+use aoc::{input_lines, Part};
 use clap::Parser;
 
 fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<usize>> {
@@ -11,11 +11,24 @@ fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<usize>> {
 
 #[derive(Debug, Parser)]
 struct Cli {
-    #[arg(short, long)]
+    #[arg(short, long, default_value = "d22.txt")]
     input: String,
 
+    #[arg(short, long, value_enum, default_value_t)]
+    part: Part,
+
     #[arg(short, long, default_value = None)]
     secret: Option<usize>,
+
+    /// Number of new secrets each buyer generates (default 2000, or 10 with
+    /// --secret)
+    #[arg(short, long)]
+    generations: Option<usize>,
+
+    /// Note anything unusual about the input, such as repeated seeds; with
+    /// --secret, show the buyer's prices and first few change sequences
+    #[arg(short, long)]
+    verbose: bool,
 }
 
 fn mix(secret: usize, number: usize) -> usize {
@@ -26,39 +39,187 @@ fn prune(secret: usize) -> usize {
     secret % 16777216
 }
 
+fn next_secret(secret: usize) -> usize {
+    // mul 64
+    let mut value = secret * 64;
+    let mut secret = prune(mix(value, secret));
+
+    // div 32
+    value = secret / 32;
+    secret = prune(mix(value, secret));
+
+    // mul 2048
+    value = secret * 2048;
+    prune(mix(value, secret))
+}
+
+// The seed followed by each secret generated from it
+fn secret_iter(seed: usize) -> impl Iterator<Item = usize> {
+    std::iter::successors(Some(seed), |&secret| Some(next_secret(secret)))
+}
+
 fn simulate(base_secret: usize, generations: usize) -> usize {
     let mut secret = base_secret;
     for _gen in 0..generations {
-        // mul 64
-        let mut value = secret * 64;
-        secret = prune(mix(value, secret));
+        secret = next_secret(secret);
+    }
+
+    secret
+}
 
-        // div 32
-        value = secret / 32;
-        secret = prune(mix(value, secret));
+// How many of the 4-change sequences to show in `print_prices`
+const SHOWN_WINDOWS: usize = 5;
 
-        // mul 2048
-        value = secret * 2048;
-        secret = prune(mix(value, secret));
+// Print the price (and change from the previous price) for each secret, like
+// the table in the puzzle, followed by the first few sequences of 4 changes
+// along with the price the buyer would sell at for each.
+fn print_prices(seed: usize, generations: usize) {
+    let prices: Vec<(usize, isize)> = secret_iter(seed)
+        .take(generations + 1)
+        .map(|secret| (secret, (secret % 10) as isize))
+        .collect();
+    let changes: Vec<isize> = prices.windows(2).map(|w| w[1].1 - w[0].1).collect();
+
+    for (gen, (secret, price)) in prices.iter().enumerate() {
+        match gen.checked_sub(1) {
+            Some(prev) => println!("{secret:>10}: {price} ({})", changes[prev]),
+            None => println!("{secret:>10}: {price}"),
+        }
     }
 
-    secret
+    for (idx, window) in changes.windows(4).take(SHOWN_WINDOWS).enumerate() {
+        let price = prices[idx + 4].1;
+        println!("{window:?} => {price}");
+    }
+}
+
+// Price changes are always in -9..=9, so a window of 4 changes can be packed
+// as a base-19 number and used to index directly into a flat array rather
+// than hashing tuples.
+const CHANGE_RADIX: usize = 19;
+const WINDOW_COMBOS: usize = CHANGE_RADIX * CHANGE_RADIX * CHANGE_RADIX * CHANGE_RADIX;
+
+fn decode_window(mut idx: usize) -> [isize; 4] {
+    let mut changes = [0; 4];
+    for change in changes.iter_mut().rev() {
+        *change = (idx % CHANGE_RADIX) as isize - 9;
+        idx /= CHANGE_RADIX;
+    }
+    changes
+}
+
+// Find the sequence of 4 price changes that earns the most bananas across all
+// buyers, returning the total along with the sequence.  Each buyer sells on
+// the first occurrence of the sequence only, which we enforce by tracking
+// which windows we've already seen for the buyer in a bitset.  Every seed is
+// its own buyer, even if the same seed appears more than once.
+fn best_sequence(seeds: &[usize], generations: usize) -> (usize, [isize; 4]) {
+    let mut bananas = vec![0usize; WINDOW_COMBOS];
+    let mut seen = vec![0u64; WINDOW_COMBOS.div_ceil(64)];
+    for &seed in seeds {
+        seen.fill(0);
+        let mut secret = seed;
+        let mut window = 0;
+        for gen in 0..generations {
+            let next = next_secret(secret);
+            let (price, next_price) = ((secret % 10) as isize, (next % 10) as isize);
+            let change = (next_price - price + 9) as usize;
+
+            // roll the window forward, dropping the oldest change
+            window = (window * CHANGE_RADIX + change) % WINDOW_COMBOS;
+            secret = next;
+            if gen < 3 {
+                continue; // don't have 4 changes yet
+            }
+
+            let (word, bit) = (window / 64, 1 << (window % 64));
+            if seen[word] & bit == 0 {
+                seen[word] |= bit;
+                bananas[window] += next_price as usize;
+            }
+        }
+    }
+
+    let (best_window, best_bananas) = bananas
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, count)| **count)
+        .expect("Should have at least one window");
+    (*best_bananas, decode_window(best_window))
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     if let Some(secret) = cli.secret {
-        let res = simulate(secret, 10);
+        let generations = cli.generations.unwrap_or(10);
+        if cli.verbose {
+            print_prices(secret, generations);
+        }
+        let res = simulate(secret, generations);
         println!("Final Generation: {res}");
         return Ok(());
     }
     let input = parse_input(cli.input)?;
-    let mut sum_of_secrets = 0;
-    for secret in input {
-        let nth_secret = simulate(secret, 2000);
-        sum_of_secrets += nth_secret;
-        println!("{secret}: {nth_secret}");
+    let generations = cli.generations.unwrap_or(2000);
+    if cli.verbose {
+        let mut seed_counts: HashMap<usize, usize> = HashMap::new();
+        for &seed in input.iter() {
+            *seed_counts.entry(seed).or_default() += 1;
+        }
+        let mut duplicates: Vec<(usize, usize)> =
+            seed_counts.into_iter().filter(|(_, count)| *count > 1).collect();
+        duplicates.sort();
+        for (seed, count) in duplicates {
+            println!("Note: seed {seed} appears {count} times; each is treated as a separate buyer");
+        }
+    }
+    if cli.part.part1() {
+        let mut sum_of_secrets = 0;
+        for &secret in input.iter() {
+            let nth_secret = simulate(secret, generations);
+            sum_of_secrets += nth_secret;
+            println!("{secret}: {nth_secret}");
+        }
+        println!("Part 1: {sum_of_secrets}");
+    }
+
+    if cli.part.part2() {
+        let (bananas, sequence) = best_sequence(&input, generations);
+        println!("Best Sequence: {sequence:?}");
+        println!("Part 2: {bananas}");
     }
-    println!("Sum: {sum_of_secrets}");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_best_sequence() {
+        assert_eq!(best_sequence(&[1, 2, 3, 2024], 2000), (23, [-2, 1, -1, 3]));
+    }
+
+    #[test]
+    fn example_secrets() {
+        let secrets: Vec<usize> = [1, 10, 100, 2024]
+            .into_iter()
+            .map(|seed| simulate(seed, 2000))
+            .collect();
+        assert_eq!(secrets, [8685429, 4700978, 15273692, 8667524]);
+        assert_eq!(secrets.iter().sum::<usize>(), 37327623);
+    }
+
+    // A repeated seed is a second buyer with the same prices, so it sells
+    // again and doubles what that buyer brings in.
+    #[test]
+    fn duplicate_seeds() {
+        assert_eq!(best_sequence(&[123], 10), (6, [-1, -1, 0, 2]));
+        assert_eq!(best_sequence(&[123, 123], 10), (12, [-1, -1, 0, 2]));
+
+        let (single, _) = best_sequence(&[1, 2, 3, 2024], 2000);
+        let (doubled, sequence) = best_sequence(&[1, 2, 3, 2024, 1, 2, 3, 2024], 2000);
+        assert_eq!(doubled, 2 * single);
+        assert_eq!(sequence, [-2, 1, -1, 3]);
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    fmt::Display,
     fs::File,
     io::BufReader,
     path::{Path, PathBuf},
@@ -8,6 +9,8 @@ const MUL_RE: &str = r"mul\((?<a>\d+),(?<b>\d+)\)";
 const DO_RE: &str = r"do\(\)";
 const DONT_RE: &str = r"don\'t\(\)";
 
+use clap::Parser;
+
 fn parse_input<P: AsRef<Path>>(input_path: P) -> anyhow::Result<String> {
     let full_input_path = PathBuf::from(".").join("inputs").join(input_path);
     let f = File::open(full_input_path)?;
@@ -29,7 +32,7 @@ mod p1 {
             muls.push((a, b));
         }
         let sum: u32 = muls.into_iter().map(|(a, b)| a * b).sum();
-        println!("Part1: Sum of muls: {sum}");
+        println!("Part 1: {sum}");
         Ok(())
     }
 }
@@ -39,38 +42,108 @@ mod p2 {
 
     use super::*;
 
-    pub fn part2() -> anyhow::Result<()> {
+    /// A `mul` instruction found in the memory along with where it was and
+    /// whether it was enabled at that point.
+    #[derive(Debug)]
+    pub struct MulMatch {
+        pub start: usize,
+        pub a: u32,
+        pub b: u32,
+        pub enabled: bool,
+    }
+
+    impl Display for MulMatch {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let Self { start, a, b, .. } = self;
+            if self.enabled {
+                write!(f, "@{start} mul({a},{b})={} [enabled]", a * b)
+            } else {
+                write!(f, "@{start} mul({a},{b}) [disabled]")
+            }
+        }
+    }
+
+    pub fn find_muls(input: &str) -> anyhow::Result<Vec<MulMatch>> {
         let combo_re = Regex::new(&format!(
             "(?<mul>{MUL_RE})|(?<do>{DO_RE})|(?<dont>{DONT_RE})"
         ))?;
-        let input = parse_input("d3-p1.txt")?;
 
         // use the match set for the first pass to figure out enabled/disabled; if
         // things are enabled then parse out the mul match
-        let mut muls: Vec<(u32, u32)> = Vec::new();
+        let mut muls: Vec<MulMatch> = Vec::new();
         let mut enabled = true;
-        for caps in combo_re.captures_iter(&input) {
-            if let Some(_mul) = caps.name("mul") {
+        for caps in combo_re.captures_iter(input) {
+            if let Some(mul) = caps.name("mul") {
                 let a = caps["a"].parse::<u32>()?;
                 let b = caps["b"].parse::<u32>()?;
-                if enabled {
-                    muls.push((a, b));
-                }
+                muls.push(MulMatch {
+                    start: mul.start(),
+                    a,
+                    b,
+                    enabled,
+                });
             } else if let Some(_do) = caps.name("do") {
                 enabled = true;
             } else if let Some(_dont) = caps.name("dont") {
                 enabled = false;
             }
         }
+        Ok(muls)
+    }
 
-        let sum: u32 = muls.into_iter().map(|(a, b)| a * b).sum();
-        println!("Part2: Sum of enabled muls: {sum}");
+    pub fn part2(trace: bool) -> anyhow::Result<()> {
+        let input = parse_input("d3-p1.txt")?;
+        let muls = find_muls(&input)?;
+        if trace {
+            for mul in muls.iter() {
+                println!("{mul}");
+            }
+        }
+
+        let sum: u32 = muls
+            .into_iter()
+            .filter(|m| m.enabled)
+            .map(|m| m.a * m.b)
+            .sum();
+        println!("Part 2: {sum}");
         Ok(())
     }
 }
 
+#[derive(Debug, Parser)]
+struct Cli {
+    /// Print each mul in part 2 with its offset and whether it was counted
+    #[arg(long)]
+    trace: bool,
+}
+
 fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
     p1::part1()?;
-    p2::part2()?;
+    p2::part2(cli.trace)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_P2: &str =
+        "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+    const EXAMPLE_P2_TRACE: [&str; 4] = [
+        "@1 mul(2,4)=8 [enabled]",
+        "@28 mul(5,5) [disabled]",
+        "@48 mul(11,8) [disabled]",
+        "@64 mul(8,5)=40 [enabled]",
+    ];
+
+    #[test]
+    fn example_trace() {
+        let trace: Vec<String> = p2::find_muls(EXAMPLE_P2)
+            .unwrap()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        assert_eq!(trace, EXAMPLE_P2_TRACE);
+    }
+}
@@ -4,6 +4,8 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use clap::Parser;
+
 fn parse_input<P>(path: P) -> anyhow::Result<Vec<String>>
 where
     P: AsRef<Path>,
@@ -19,112 +21,79 @@ where
 }
 
 mod p1 {
-    use super::*;
-
-    const XMAS: &str = "XMAS";
-
-    fn find_ltr(puzzle: &[String]) -> usize {
-        puzzle.iter().map(|line| line.matches(XMAS).count()).sum()
-    }
-
-    fn find_rtl(puzzle: &[String]) -> usize {
-        puzzle
-            .iter()
-            .map(|line| line.chars().rev().collect::<String>())
-            .map(|revline| revline.matches(XMAS).count())
-            .sum()
-    }
-
-    fn find_in_rows(puzzle: &[String]) -> usize {
-        find_ltr(puzzle) + find_rtl(puzzle)
-    }
+    use aoc::grid::{Grid, Point};
+    use rayon::prelude::*;
 
-    fn find_in_cols(puzzle: &[String]) -> usize {
-        let cols: Vec<String> = (0..puzzle.len())
-            .map(|col_idx| {
-                puzzle
-                    .iter()
-                    .map(|row| row.chars().nth(col_idx).unwrap())
-                    .collect::<String>()
-            })
-            .collect();
+    pub const XMAS: &str = "XMAS";
 
-        // now that we've flipped the puzzle, it's the
-        // same puzzle as doing a row search
-        find_ltr(&cols) + find_rtl(&cols)
-    }
+    const DIRECTIONS: [(isize, isize); 8] = [
+        (0, 1),
+        (0, -1),
+        (1, 0),
+        (-1, 0),
+        (1, 1),
+        (1, -1),
+        (-1, 1),
+        (-1, -1),
+    ];
 
-    fn tl_to_br_diagonals(puzzle: &[String]) -> Vec<String> {
-        // for this first pass, assume rows/cols are equal;
-        // we'll address that issue later.
-        let col_count = puzzle[0].len();
-        let row_count = puzzle.len();
-        let mut diags = Vec::new();
-
-        // first pass fills half the diagonals; there's probably a
-        // better way to do this, but this is how it ended up after
-        // my first idea of an algorithm on paper.
-        for col_idx in 0..col_count {
-            let mut diag = String::new();
-            for row_idx in 0..(row_count - col_idx) {
-                // row/col at same index is on the ltr diagonal
-                let row = &puzzle[row_idx];
-                if let Some(c) = row.chars().nth(row_idx + col_idx) {
-                    diag.push(c);
+    // Does the word appear starting at (row, col) heading in (drow, dcol)?
+    fn word_at(
+        puzzle: &Grid<char>,
+        word: &[char],
+        row: usize,
+        col: usize,
+        drow: isize,
+        dcol: isize,
+    ) -> bool {
+        let (mut row, mut col) = (row, col);
+        for (idx, &expected) in word.iter().enumerate() {
+            if idx > 0 {
+                match (row.checked_add_signed(drow), col.checked_add_signed(dcol)) {
+                    (Some(r), Some(c)) => (row, col) = (r, c),
+                    _ => return false,
                 }
             }
-            diags.push(diag);
-        }
-
-        // second pass is to fill in the the remaining one diagonal
-        // per row
-        for row_idx in 1..row_count {
-            let mut diag = String::new();
-            for level_idx in 0..(col_count - row_idx) {
-                let row = &puzzle[row_idx + level_idx];
-                if let Some(c) = row.chars().nth(level_idx) {
-                    diag.push(c);
-                }
+            if puzzle.get(Point::new(col, row)) != Some(&expected) {
+                return false;
             }
-            diags.push(diag);
         }
-
-        diags
-    }
-
-    fn tr_to_bl_diagonals(puzzle: &[String]) -> Vec<String> {
-        // flipping the rows around and using the same tl->br algo
-        // gives us the tr->bl results we want
-        let flipped_puzzle = puzzle.iter().rev().cloned().collect::<Vec<String>>();
-
-        tl_to_br_diagonals(&flipped_puzzle)
+        true
     }
 
-    fn find_diagonal(puzzle: &[String]) -> usize {
-        // do some transform to get iterators over diagonals;
-        // we can break down the types of diagnols into:
-        // 1. TL to BR
-        // 2. BR to TL (1 reversed)
-        // 3. TR to BL
-        // 4. BL to TR (3 reversed)
-        let tl_to_br = tl_to_br_diagonals(&puzzle);
-        let tr_to_bl = tr_to_bl_diagonals(&puzzle);
-
-        // case 1 and 2n
-        find_ltr(&tl_to_br) + find_rtl(&tl_to_br) + find_ltr(&tr_to_bl) + find_rtl(&tr_to_bl)
+    // Search for the word in all 8 directions by stepping through the grid
+    // from each origin rather than building up strings for each row, column,
+    // and diagonal; rows are searched in parallel.
+    pub fn count_in_place(puzzle: &Grid<char>, word: &str) -> usize {
+        let word: Vec<char> = word.chars().collect();
+        (0..puzzle.height())
+            .into_par_iter()
+            .map(|row| {
+                let mut count = 0;
+                for col in 0..puzzle.width() {
+                    for (drow, dcol) in DIRECTIONS {
+                        if word_at(puzzle, &word, row, col, drow, dcol) {
+                            count += 1;
+                        }
+                    }
+                }
+                count
+            })
+            .sum()
     }
 
-    pub fn part1() -> anyhow::Result<()> {
-        let puzzle = parse_input("d4-p1.txt")?;
-        let total = find_in_rows(&puzzle) + find_in_cols(&puzzle) + find_diagonal(&puzzle);
-        println!("Found XMAS {total} times");
-        Ok(())
+    pub fn part1(puzzle: &[String]) -> usize {
+        let grid = Grid::from_rows(
+            puzzle
+                .iter()
+                .map(|r| r.chars().collect::<Vec<char>>())
+                .collect(),
+        );
+        count_in_place(&grid, XMAS)
     }
 }
 
 mod p2 {
-    use crate::parse_input;
-
     // In this part, we're looking for X-MAS as in "M A S" in the form
     // of an X (sigh).  For this one, we're just going to brute search
     // for 3x3 grids that have one of the possible valid sets of positions
@@ -168,18 +137,14 @@ mod p2 {
         ],
     ];
 
-    pub fn part2() -> anyhow::Result<()> {
-        let puzzle = parse_input("d4-p1.txt")?;
-        let puzarr = puzzle
-            .into_iter()
-            .map(|r| r.chars().collect::<Vec<char>>())
-            .collect::<Vec<Vec<char>>>();
-
+    // The (row, col) of the 'A' at the center of every X-MAS; crosses may
+    // overlap and share letters, each is found from its own 3x3 window.
+    pub fn find_xmas_centers(puzarr: &[Vec<char>]) -> Vec<(usize, usize)> {
         let row_count = puzarr.len();
-        let col_count = puzarr[0].len();
-        let mut matches = 0;
-        for row_idx in 0..(row_count - 2) {
-            for col_idx in 0..(col_count - 2) {
+        let col_count = puzarr.first().map(|r| r.len()).unwrap_or(0);
+        let mut centers = Vec::new();
+        for row_idx in 0..row_count.saturating_sub(2) {
+            for col_idx in 0..col_count.saturating_sub(2) {
                 'pattern: for &pattern in PATTERNS {
                     for (xoff, yoff, candidate_c) in pattern {
                         let c = puzarr[row_idx + xoff][col_idx + yoff];
@@ -189,19 +154,135 @@ mod p2 {
                     }
 
                     // if we made it through, we have a match
-                    matches += 1;
+                    centers.push((row_idx + 1, col_idx + 1));
                 }
             }
         }
+        centers
+    }
 
-        println!("Found {matches} matches!");
+    pub fn part2(puzzle: &[String]) -> usize {
+        let puzarr = puzzle
+            .iter()
+            .map(|r| r.chars().collect::<Vec<char>>())
+            .collect::<Vec<Vec<char>>>();
 
-        Ok(())
+        find_xmas_centers(&puzarr).len()
     }
 }
 
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d4-p1.txt")]
+    input: String,
+}
+
 fn main() -> anyhow::Result<()> {
-    p1::part1()?;
-    p2::part2()?;
+    let cli = Cli::parse();
+    let puzzle = parse_input(&cli.input)?;
+    println!("Part 1: {}", p1::part1(&puzzle));
+    println!("Part 2: {}", p2::part2(&puzzle));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc::{grid::Grid, testing::XorShift};
+
+    // The full example word search, before the letters not in any XMAS (or
+    // X-MAS) are replaced by dots as in d4-example1.txt and d4-example2.txt.
+    const EXAMPLE: &str = "\
+MMMSXXMASM
+MSAMXMSMSA
+AMXSXMAAMM
+MSAMASMSMX
+XMASAMXAMM
+XXAMMXXAMA
+SMSMSASXSS
+SAXAMASAAA
+MAMMMXMMMM
+MXMXAXMASX";
+    const EXAMPLE_XMAS: usize = 18;
+    const EXAMPLE_X_MAS: usize = 9;
+
+    fn as_lines<'a>(cells: impl Iterator<Item = &'a [char]>) -> Vec<String> {
+        cells.map(|line| line.iter().collect::<String>()).collect()
+    }
+
+    fn diagonal_lines(puzzle: &Grid<char>) -> Vec<String> {
+        puzzle
+            .diagonals()
+            .map(|diag| diag.map(|(_, c)| *c).collect::<String>())
+            .collect()
+    }
+
+    // Every one of the 8 directions read left to right along the rows or
+    // down the diagonals of the puzzle or one of its transforms:
+    // - rows of the puzzle and its three rotations: east, north, west, south
+    // - diagonals of the puzzle: south-east and south-west
+    // - diagonals of the puzzle flipped top to bottom: north-east and north-west
+    fn search_lines(puzzle: &Grid<char>) -> Vec<String> {
+        let cw = puzzle.rotate_cw();
+        let mut lines = as_lines(puzzle.rows());
+        lines.extend(as_lines(cw.rows()));
+        lines.extend(as_lines(cw.rotate_cw().rows()));
+        lines.extend(as_lines(puzzle.rotate_ccw().rows()));
+        lines.extend(diagonal_lines(puzzle));
+        lines.extend(diagonal_lines(&puzzle.flip_rows()));
+        lines
+    }
+
+    fn count_in_lines(lines: &[String], word: &str) -> usize {
+        lines.iter().map(|line| line.matches(word).count()).sum()
+    }
+
+    fn grid(rows: &[String]) -> Grid<char> {
+        Grid::from_rows(rows.iter().map(|r| r.chars().collect()).collect())
+    }
+
+    #[test]
+    fn in_place_search_matches_transforms_on_example() {
+        let puzzle = grid(&EXAMPLE.lines().map(String::from).collect::<Vec<_>>());
+        let expected = count_in_lines(&search_lines(&puzzle), p1::XMAS);
+        assert_eq!(expected, EXAMPLE_XMAS);
+        assert_eq!(p1::count_in_place(&puzzle, p1::XMAS), expected);
+    }
+
+    #[test]
+    fn in_place_search_matches_transforms_on_random_grid() {
+        let mut rng = XorShift(0x5eed);
+        let letters = ['X', 'M', 'A', 'S'];
+        let rows: Vec<String> = (0..40)
+            .map(|_| (0..60).map(|_| letters[rng.below(letters.len())]).collect())
+            .collect();
+        let puzzle = grid(&rows);
+        let expected = count_in_lines(&search_lines(&puzzle), p1::XMAS);
+        assert!(expected > 0);
+        assert_eq!(p1::count_in_place(&puzzle, p1::XMAS), expected);
+    }
+
+    #[test]
+    fn overlapping_x_mas_centers() {
+        // two crosses sharing the middle column, in a grid wider than it is tall
+        let puzzle: Vec<Vec<char>> = ["MXMXM", "XAXAX", "SXSXS"]
+            .iter()
+            .map(|r| r.chars().collect())
+            .collect();
+        assert_eq!(p2::find_xmas_centers(&puzzle), [(1, 1), (1, 3)]);
+
+        // and the same pair stood on its side
+        let puzzle: Vec<Vec<char>> = ["MXS", "XAX", "MXS", "XAX", "MXS"]
+            .iter()
+            .map(|r| r.chars().collect())
+            .collect();
+        assert_eq!(p2::find_xmas_centers(&puzzle), [(1, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn example() {
+        let puzzle: Vec<String> = EXAMPLE.lines().map(String::from).collect();
+        assert_eq!(p1::part1(&puzzle), EXAMPLE_XMAS);
+        assert_eq!(p2::part2(&puzzle), EXAMPLE_X_MAS);
+    }
+}
@@ -6,7 +6,9 @@ use std::{
     path::{Path, PathBuf},
 };
 
-#[derive(Debug)]
+use clap::Parser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct OrderingRule {
     first: usize,
     second: usize,
@@ -62,39 +64,50 @@ fn parse_inputs<P: AsRef<Path>>(path: P) -> anyhow::Result<Inputs> {
     })
 }
 
-fn part1_and_2() -> anyhow::Result<()> {
-    let inputs = parse_inputs("d5-p1.txt")?;
+fn part1_and_2(input: &str, explain: bool) -> anyhow::Result<()> {
+    let inputs = parse_inputs(input)?;
     let Inputs {
         ordering_rules,
         page_orderings,
     } = inputs;
 
-    let mut good_orderings = Vec::new();
-    let mut bad_orderings = Vec::new();
-    'page_ordering: for page_ordering in page_orderings.iter() {
-        for ordering_rule in ordering_rules.iter() {
-            let mut first_seen = false;
-            let mut second_seen = false;
-            let mut second_seen_first = false;
-            for &page in page_ordering {
-                if page == ordering_rule.first {
-                    first_seen = true;
-                }
+    // build the set of (first, second) pairs once; both the classification
+    // and the reordering just need to ask if a given pair is a rule.
+    let rules = Rules::new(&ordering_rules);
 
-                if page == ordering_rule.second {
-                    second_seen = true;
-                    if !first_seen {
-                        second_seen_first = true;
-                    }
-                }
-            }
+    // reordering only has a single answer if the rules fully order every
+    // update, so check that up front.
+    if let Some(ordering) = page_orderings
+        .iter()
+        .find(|ordering| !rules.is_total_order(ordering))
+    {
+        anyhow::bail!("Rules do not give a single order for update {ordering:?}");
+    }
 
-            if first_seen && second_seen && second_seen_first {
-                bad_orderings.push(page_ordering);
-                continue 'page_ordering;
+    let mut good_orderings = Vec::new();
+    let mut bad_orderings = Vec::new();
+    for page_ordering in page_orderings.iter() {
+        let violated = violations(&rules, page_ordering);
+        if explain {
+            let pages = page_ordering
+                .iter()
+                .map(|page| page.to_string())
+                .collect::<Vec<_>>();
+            if violated.is_empty() {
+                println!("{}: OK", pages.join(","));
+            } else {
+                let violated = violated
+                    .iter()
+                    .map(|rule| rule.to_string())
+                    .collect::<Vec<_>>();
+                println!("{}: violates {}", pages.join(","), violated.join(", "));
             }
         }
-        good_orderings.push(page_ordering);
+        if violated.is_empty() {
+            good_orderings.push(page_ordering);
+        } else {
+            bad_orderings.push(page_ordering);
+        }
     }
 
     let middle_pages_sum: usize = good_orderings
@@ -109,9 +122,9 @@ fn part1_and_2() -> anyhow::Result<()> {
         })
         .sum();
 
-    println!("Part 1: um of good ordering middle pages: {middle_pages_sum}");
+    println!("Part 1: {middle_pages_sum}");
 
-    let reordered_updates = fix_page_orderings(&ordering_rules, bad_orderings.as_slice());
+    let reordered_updates = fix_page_orderings(&rules, bad_orderings.as_slice());
     let reordered_pages_mid_sum: usize = reordered_updates
         .into_iter()
         .map(|ordering| {
@@ -119,12 +132,68 @@ fn part1_and_2() -> anyhow::Result<()> {
             mid
         })
         .sum();
-    println!("Part 2: sum of reordered middle pages: {reordered_pages_mid_sum}");
+    println!("Part 2: {reordered_pages_mid_sum}");
 
     Ok(())
 }
 
-fn fix_page_ordering(rules: &[OrderingRule], bad_ordering: &[usize]) -> Vec<usize> {
+/// The ordering relation given by the rules as a set of (first, second) pairs.
+struct Rules(HashSet<(usize, usize)>);
+
+impl Rules {
+    fn new(rules: &[OrderingRule]) -> Self {
+        Rules(rules.iter().map(|rule| (rule.first, rule.second)).collect())
+    }
+
+    /// Is there a rule that `a` must come before `b`?
+    fn before(&self, a: usize, b: usize) -> bool {
+        self.0.contains(&(a, b))
+    }
+
+    /// Do the rules put these pages in exactly one order?  That requires a
+    /// rule one way (and not the other) for every pair of pages and no
+    /// cycles; for such a relation the number of pages each page must come
+    /// before is unique, running from 0 for the last page up to len - 1.
+    fn is_total_order(&self, pages: &[usize]) -> bool {
+        let mut precedes_count: Vec<usize> = Vec::with_capacity(pages.len());
+        for (idx, &a) in pages.iter().enumerate() {
+            let mut count = 0;
+            for (other_idx, &b) in pages.iter().enumerate() {
+                if idx == other_idx {
+                    continue;
+                }
+                if self.before(a, b) == self.before(b, a) {
+                    return false; // no rule or a contradiction
+                }
+                if self.before(a, b) {
+                    count += 1;
+                }
+            }
+            precedes_count.push(count);
+        }
+        precedes_count.sort();
+        precedes_count.into_iter().eq(0..pages.len())
+    }
+}
+
+// The rules broken by an ordering, i.e. those where both pages are present
+// but the second comes first; a good ordering breaks none.
+fn violations(rules: &Rules, ordering: &[usize]) -> Vec<OrderingRule> {
+    let mut violated = Vec::new();
+    for (idx, &earlier) in ordering.iter().enumerate() {
+        for &later in &ordering[idx + 1..] {
+            if rules.before(later, earlier) {
+                violated.push(OrderingRule {
+                    first: later,
+                    second: earlier,
+                });
+            }
+        }
+    }
+    violated
+}
+
+fn fix_page_ordering(rules: &Rules, bad_ordering: &[usize]) -> Vec<usize> {
     // we might have multiple changes needed, so what algorithm should we use?
     //
     // Naively, we could just swap the first/second if there is a violation until
@@ -133,19 +202,11 @@ fn fix_page_ordering(rules: &[OrderingRule], bad_ordering: &[usize]) -> Vec<usiz
 
     // filter rules to be only those that apply to our series
     let mut filtered_rules = Vec::new();
-    for rule in rules {
-        let mut first_seen = false;
-        let mut second_seen = false;
-        for &page in bad_ordering {
-            if page == rule.first {
-                first_seen = true;
+    for &first in bad_ordering {
+        for &second in bad_ordering {
+            if rules.before(first, second) {
+                filtered_rules.push(OrderingRule { first, second });
             }
-            if page == rule.second {
-                second_seen = true;
-            }
-        }
-        if first_seen && second_seen {
-            filtered_rules.push(rule);
         }
     }
 
@@ -226,14 +287,92 @@ fn fix_page_ordering(rules: &[OrderingRule], bad_ordering: &[usize]) -> Vec<usiz
     good_ordering
 }
 
-fn fix_page_orderings(rules: &[OrderingRule], bad_orderings: &[&Vec<usize>]) -> Vec<Vec<usize>> {
+fn fix_page_orderings(rules: &Rules, bad_orderings: &[&Vec<usize>]) -> Vec<Vec<usize>> {
     bad_orderings
         .into_iter()
         .map(|ordering| fix_page_ordering(rules, &ordering))
         .collect::<Vec<Vec<usize>>>()
 }
 
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d5-p1.txt")]
+    input: String,
+
+    /// Print each update with the rules it breaks, if any
+    #[arg(short, long)]
+    explain: bool,
+}
+
 fn main() -> anyhow::Result<()> {
-    part1_and_2()?;
+    let cli = Cli::parse();
+    part1_and_2(&cli.input, cli.explain)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(pairs: &[(usize, usize)]) -> Rules {
+        let rules: Vec<OrderingRule> = pairs
+            .iter()
+            .map(|&(first, second)| OrderingRule { first, second })
+            .collect();
+        Rules::new(&rules)
+    }
+
+    #[test]
+    fn before() {
+        let inputs = parse_inputs("d5-example1.txt").unwrap();
+        let rules = Rules::new(&inputs.ordering_rules);
+        assert!(rules.before(47, 53));
+        assert!(!rules.before(53, 47));
+        assert!(rules.before(97, 47));
+        assert!(!rules.before(47, 97));
+        // pages with no rule between them come before nothing
+        assert!(!rules.before(47, 47));
+        assert!(!rules.before(1, 2));
+    }
+
+    #[test]
+    fn example_updates_are_totally_ordered() {
+        let inputs = parse_inputs("d5-example1.txt").unwrap();
+        let rules = Rules::new(&inputs.ordering_rules);
+        for update in inputs.page_orderings.iter() {
+            assert!(rules.is_total_order(update), "{update:?}");
+        }
+    }
+
+    #[test]
+    fn not_a_total_order() {
+        // no rule for page 3
+        assert!(!rules(&[(1, 2)]).is_total_order(&[1, 2, 3]));
+        // rules both ways
+        assert!(!rules(&[(1, 2), (2, 1)]).is_total_order(&[1, 2]));
+        // a rule for every pair, but in a cycle
+        assert!(!rules(&[(1, 2), (2, 3), (3, 1)]).is_total_order(&[1, 2, 3]));
+        assert!(rules(&[(1, 2), (2, 3), (1, 3)]).is_total_order(&[3, 1, 2]));
+    }
+
+    #[test]
+    fn violations_of_example_updates() {
+        let inputs = parse_inputs("d5-example1.txt").unwrap();
+        let rules = Rules::new(&inputs.ordering_rules);
+        assert!(violations(&rules, &[75, 47, 61, 53, 29]).is_empty());
+        assert_eq!(
+            violations(&rules, &[75, 97, 47, 61, 53]),
+            [OrderingRule {
+                first: 97,
+                second: 75
+            }]
+        );
+        assert_eq!(
+            violations(&rules, &[61, 13, 29]),
+            [OrderingRule {
+                first: 29,
+                second: 13
+            }]
+        );
+    }
+}
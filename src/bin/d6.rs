@@ -4,56 +4,25 @@
 // How many distinct positions will the guard visit before leaving the mapped area?
 
 use std::{
+    collections::HashSet,
     fmt::Display,
     fs::File,
     io::{BufRead, BufReader},
     path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
-use enumset::{EnumSet, EnumSetType};
-
-#[derive(Debug, EnumSetType)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
-impl TryFrom<char> for Direction {
-    type Error = String;
-
-    fn try_from(value: char) -> Result<Self, Self::Error> {
-        let dir = match value {
-            '^' => Self::Up,
-            'v' => Self::Down,
-            '>' => Self::Right,
-            '<' => Self::Left,
-            other => {
-                return Err(format!("'{other}' not an expected direction input"));
-            }
-        };
-        Ok(dir)
-    }
-}
-
-impl Direction {
-    fn as_char(&self) -> char {
-        match *self {
-            Direction::Up => '^',
-            Direction::Down => 'v',
-            Direction::Left => '<',
-            Direction::Right => '>',
-        }
-    }
-}
+use aoc::direction::{Direction, DirectionSet};
+use clap::Parser;
+use rayon::prelude::*;
 
 #[derive(Debug, Clone)]
 enum MapPosition {
     Empty,
     Obstacle,
-    Visited(EnumSet<Direction>),
+    Visited(DirectionSet),
     Guard(Direction),
+    LoopObstacle, // a new obstacle that would trap the guard (for display)
 }
 
 type Map = Vec<Vec<MapPosition>>;
@@ -78,11 +47,11 @@ impl Display for MapPosition {
             Self::Empty => '.',
             Self::Obstacle => '#',
             Self::Visited(dirset) => {
-                let mapping: &[(EnumSet<Direction>, char)] = &[
-                    (EnumSet::from(Direction::Up), '^'),
-                    (EnumSet::from(Direction::Down), 'v'),
-                    (EnumSet::from(Direction::Left), '<'),
-                    (EnumSet::from(Direction::Right), '>'),
+                let mapping: &[(DirectionSet, char)] = &[
+                    (DirectionSet::only(Direction::Up), '^'),
+                    (DirectionSet::only(Direction::Down), 'v'),
+                    (DirectionSet::only(Direction::Left), '<'),
+                    (DirectionSet::only(Direction::Right), '>'),
                     ((Direction::Up | Direction::Down), '|'),
                     ((Direction::Left | Direction::Right), '-'),
                     ((Direction::Up | Direction::Right), 'L'),
@@ -97,6 +66,7 @@ impl Display for MapPosition {
                     .unwrap_or_else(|| '+')
             }
             Self::Guard(direction) => direction.as_char(),
+            Self::LoopObstacle => 'O',
         };
         write!(f, "{}", c)
     }
@@ -123,7 +93,18 @@ fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Map> {
     Ok(map)
 }
 
-fn simulate_movements(orig_map: &Map) -> Option<Map> {
+/// How a simulated walk of the guard ended.
+enum SimOutcome {
+    /// The guard walked off the map; the map has every visited cell marked
+    Exited(Map),
+    /// The guard came back to a cell heading the same way as before
+    Cycle,
+    /// The guard is boxed in and can only turn in place, or the walk went on
+    /// for longer than it could without repeating (which shouldn't happen)
+    StuckOrUnknown,
+}
+
+fn simulate_movements(orig_map: &Map) -> SimOutcome {
     // find guard position
     let mut map = orig_map.clone();
     struct GuardPosition {
@@ -154,46 +135,54 @@ fn simulate_movements(orig_map: &Map) -> Option<Map> {
     let row_count = map.len();
     let col_count = map[0].len();
     map[guard_position.row][guard_position.col] =
-        MapPosition::Visited(EnumSet::from(guard_direction));
-    loop {
-        let (delta_row, delta_col) = match guard_direction {
-            Direction::Up => (-1, 0),
-            Direction::Down => (1, 0),
-            Direction::Left => (0, -1),
-            Direction::Right => (0, 1),
-        };
+        MapPosition::Visited(DirectionSet::only(guard_direction));
+    let mut turns_in_place = 0;
+    // each cell can be entered heading each way once before it's a cycle,
+    // with at most three turns in between
+    let max_steps = 16 * row_count * col_count;
+    for _ in 0..max_steps {
+        let (delta_col, delta_row) = guard_direction.dx_dy();
 
         let (next_row, next_col) = match (
             guard_position.row.checked_add_signed(delta_row),
             guard_position.col.checked_add_signed(delta_col),
         ) {
             (Some(r), Some(c)) if c < col_count && r < row_count => (r as usize, c as usize),
-            _ => break, // the guard has left the building
+            // the guard has left the building; return back the map we
+            // mutated in place
+            _ => return SimOutcome::Exited(map),
         };
 
         let next_map_element = &mut map[next_row][next_col];
         match next_map_element {
             MapPosition::Visited(dirset) => {
                 // if we've already visited this position in the same direction, then
-                // we have a cycle.  Exit with 'None' as a sentinel for a cycle.
+                // we have a cycle.
                 if dirset.contains(guard_direction) {
-                    return None;
+                    return SimOutcome::Cycle;
                 }
 
                 // add this direction to the set
                 *next_map_element = MapPosition::Visited(*dirset | guard_direction);
                 guard_position.row = next_row;
                 guard_position.col = next_col;
+                turns_in_place = 0;
             }
             MapPosition::Empty => {
                 // mark next spot as visited and put the guard in this pos
-                *next_map_element = MapPosition::Visited(EnumSet::from(guard_direction));
+                *next_map_element = MapPosition::Visited(DirectionSet::only(guard_direction));
                 guard_position.row = next_row;
                 guard_position.col = next_col;
+                turns_in_place = 0;
             }
-            MapPosition::Obstacle => {
+            MapPosition::Obstacle | MapPosition::LoopObstacle => {
                 // change direction guard is facing but the guard
-                // doesn't move this pass.
+                // doesn't move this pass; after turning all the way
+                // around, there's nowhere to go.
+                turns_in_place += 1;
+                if turns_in_place == 4 {
+                    return SimOutcome::StuckOrUnknown;
+                }
                 guard_direction = match guard_direction {
                     Direction::Up => Direction::Right,
                     Direction::Right => Direction::Down,
@@ -205,8 +194,7 @@ fn simulate_movements(orig_map: &Map) -> Option<Map> {
         }
     }
 
-    // return back the map we mutated in place
-    Some(map)
+    SimOutcome::StuckOrUnknown
 }
 
 fn print_map(map: &Map) {
@@ -218,17 +206,33 @@ fn print_map(map: &Map) {
     }
 }
 
-fn positions_visited(map: &Map) -> usize {
-    map.iter()
-        .map(|r| r.iter().filter(|&p| matches!(*p, MapPosition::Visited(_))))
+// The (row, col) of every position the guard visited
+fn visited_cells(map_with_visits: &Map) -> HashSet<(usize, usize)> {
+    map_with_visits
+        .iter()
+        .enumerate()
+        .map(|(ridx, r)| {
+            r.iter().enumerate().filter_map(move |(cidx, c)| {
+                if matches!(*c, MapPosition::Visited(_)) {
+                    Some((ridx, cidx))
+                } else {
+                    None
+                }
+            })
+        })
         .flatten()
-        .count()
+        .collect()
+}
+
+fn positions_visited(map: &Map) -> usize {
+    visited_cells(map).len()
 }
 
 fn find_single_obstacle_positions(
     orig_map: &Map,
-    map_with_visits: &Map,
-) -> Vec<(usize, usize, Map)> {
+    visited_positions: &HashSet<(usize, usize)>,
+    progress: bool,
+) -> Vec<(usize, usize)> {
     // TODO: find the number of single obstacles we could place
     //       into the map to cause the guard to get stuck
     //       indefinitely.
@@ -248,56 +252,185 @@ fn find_single_obstacle_positions(
     //    to block the cardinal direction of a move and test each of
     //    those.
 
-    let mut single_obstacle_positions: Vec<(usize, usize, Map)> = Vec::new();
-    let visited_positions = map_with_visits
-        .iter()
-        .enumerate()
-        .map(|(ridx, r)| {
-            r.iter().enumerate().filter_map(move |(cidx, c)| {
-                if matches!(*c, MapPosition::Visited(_)) {
-                    Some((ridx, cidx))
-                } else {
-                    None
-                }
-            })
+    //
+    // Each candidate is simulated independently, so they're spread across
+    // the rayon pool; a shared counter tracks how many have finished for
+    // the progress report.
+    let total = visited_positions.len();
+    let report_every = (total / 20).max(1);
+    let completed = AtomicUsize::new(0);
+    visited_positions
+        .par_iter()
+        .filter_map(|&(row, col)| {
+            // create a map with each position visited having an obstacle
+            // and see if we end up with a cycle when simulated
+            let trapped = if matches!(orig_map[row][col], MapPosition::Guard(_)) {
+                false // special case
+            } else {
+                let mut map = orig_map.clone();
+                map[row][col] = MapPosition::Obstacle;
+                matches!(simulate_movements(&map), SimOutcome::Cycle)
+            };
+
+            let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+            if progress && (done % report_every == 0 || done == total) {
+                eprintln!("Obstacles checked: {done}/{total}");
+            }
+            trapped.then_some((row, col))
         })
-        .flatten()
-        .collect::<Vec<(usize, usize)>>();
-
-    for (row, col) in visited_positions {
-        // create a map with each position visited having an obstacle
-        // and see if we end up with a cycle when simulated
-        let mut map = orig_map.clone();
-        if matches!(map[row][col], MapPosition::Guard(_)) {
-            continue; // special case
-        }
+        .collect()
+}
 
-        map[row][col] = MapPosition::Obstacle;
-        let res = simulate_movements(&map);
-        if res.is_none() {
-            single_obstacle_positions.push((row, col, map));
+// The map with the guard's route marked, as long as the guard leaves
+fn walk_off_map(map: &Map) -> anyhow::Result<Map> {
+    match simulate_movements(map) {
+        SimOutcome::Exited(map_with_visits) => Ok(map_with_visits),
+        SimOutcome::Cycle => anyhow::bail!("The guard walks in a loop on the base map"),
+        SimOutcome::StuckOrUnknown => {
+            anyhow::bail!("The guard is boxed in (or never leaves) on the base map")
         }
     }
+}
+
+/// The base map along with the guard's route off of it; both parts start
+/// from this one walk.
+struct Patrol {
+    map: Map,
+    route: Map,
+}
+
+impl Patrol {
+    /// Walk the guard off the map; a guard that loops or is boxed in on the
+    /// base map has no answer for either part.
+    fn new(map: Map) -> anyhow::Result<Self> {
+        let route = walk_off_map(&map)?;
+        Ok(Patrol { map, route })
+    }
+
+    // Part 1: the number of distinct positions the guard visits before leaving
+    fn count_visited(&self) -> usize {
+        positions_visited(&self.route)
+    }
+
+    // Part 2: the positions where a single new obstacle sends the guard in a
+    // loop; the answer is how many there are.
+    fn loop_obstacles(&self, progress: bool) -> Vec<(usize, usize)> {
+        find_single_obstacle_positions(&self.map, &visited_cells(&self.route), progress)
+    }
 
-    single_obstacle_positions
+    // Part 2 run on a pool of its own with the given number of threads,
+    // rather than rayon's global pool.
+    fn loop_obstacles_on_pool(
+        &self,
+        threads: usize,
+        progress: bool,
+    ) -> anyhow::Result<Vec<(usize, usize)>> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()?;
+        Ok(pool.install(|| self.loop_obstacles(progress)))
+    }
+}
+
+// Overlay every loop causing obstacle position onto the map
+fn render_loop_obstacles(map: &Map, obstacles: &[(usize, usize)]) -> Map {
+    let mut rendered = map.clone();
+    for (row, col) in obstacles {
+        rendered[*row][*col] = MapPosition::LoopObstacle;
+    }
+    rendered
+}
+
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d6-p1.txt")]
+    input: String,
+    /// Draw the map with each obstacle position that traps the guard marked
+    #[arg(long)]
+    show_obstacles: bool,
+    /// Report how many obstacle positions have been checked as part 2 goes
+    #[arg(long)]
+    progress: bool,
+    /// Number of threads to check obstacle positions on (defaults to one per
+    /// CPU)
+    #[arg(long)]
+    threads: Option<usize>,
 }
 
 fn main() -> anyhow::Result<()> {
-    let orig_map = parse_input("d6-p1.txt")?;
-    print_map(&orig_map);
-    let map_with_visits =
-        simulate_movements(&orig_map).expect("Base map unexpectedly simulated a cycle");
-    print_map(&map_with_visits);
-    let visited = positions_visited(&map_with_visits);
-    println!("Positions Visited: {visited}");
+    let cli = Cli::parse();
+    let patrol = Patrol::new(parse_input(&cli.input)?)?;
+    print_map(&patrol.map);
+    print_map(&patrol.route);
+    println!("Part 1: {}", patrol.count_visited());
 
     println!("");
     println!("");
-    let obstacle_sim_results = find_single_obstacle_positions(&orig_map, &map_with_visits);
-    println!(
-        "Single obstacle scenario count: {}",
-        obstacle_sim_results.len()
-    );
+    let obstacles = match cli.threads {
+        Some(threads) => patrol.loop_obstacles_on_pool(threads, cli.progress)?,
+        None => patrol.loop_obstacles(cli.progress),
+    };
+    if cli.show_obstacles {
+        print_map(&render_loop_obstacles(&patrol.map, &obstacles));
+    }
+    println!("Part 2: {}", obstacles.len());
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_ANSWERS: (usize, usize) = (41, 6);
+
+    // A guard with obstacles on all four sides can only turn in place
+    #[test]
+    fn boxed_in_guard_is_stuck() {
+        let boxed_in = parse_input("d6-boxed-in.txt").unwrap();
+        assert!(matches!(
+            simulate_movements(&boxed_in),
+            SimOutcome::StuckOrUnknown
+        ));
+        assert!(Patrol::new(boxed_in).is_err());
+    }
+
+    // A guard starting on the edge facing outward leaves on the first step
+    #[test]
+    fn edge_guard_exits() {
+        let edge_exit = parse_input("d6-edge-exit.txt").unwrap();
+        assert!(matches!(
+            simulate_movements(&edge_exit),
+            SimOutcome::Exited(_)
+        ));
+        let patrol = Patrol::new(edge_exit).unwrap();
+        assert_eq!(patrol.count_visited(), 1);
+        assert!(patrol.loop_obstacles(false).is_empty());
+    }
+
+    // The guard in the cycle map is walking in a loop from the start
+    #[test]
+    fn looping_guard_is_a_cycle() {
+        let cycle = parse_input("d6-cycle.txt").unwrap();
+        assert!(matches!(simulate_movements(&cycle), SimOutcome::Cycle));
+        assert!(Patrol::new(cycle).is_err());
+    }
+
+    #[test]
+    fn example() {
+        let patrol = Patrol::new(parse_input("d6-example1.txt").unwrap()).unwrap();
+        assert_eq!(patrol.count_visited(), EXAMPLE_ANSWERS.0);
+        assert_eq!(patrol.loop_obstacles(false).len(), EXAMPLE_ANSWERS.1);
+    }
+
+    // The loop obstacle count for the example shouldn't depend on how many
+    // threads find it or on progress being reported.
+    #[test]
+    fn example_on_pools() {
+        let patrol = Patrol::new(parse_input("d6-example1.txt").unwrap()).unwrap();
+        let serial = patrol.loop_obstacles_on_pool(1, false).unwrap();
+        let with_progress = patrol.loop_obstacles_on_pool(4, true).unwrap();
+        assert_eq!(serial.len(), EXAMPLE_ANSWERS.1);
+        assert_eq!(with_progress.len(), EXAMPLE_ANSWERS.1);
+    }
+}
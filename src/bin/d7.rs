@@ -10,11 +10,16 @@
 // 292: 11 6 16 20
 
 use std::{
+    collections::HashMap,
     fs::File,
     io::{BufRead, BufReader},
     path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
 };
 
+use clap::Parser;
+use rayon::prelude::*;
+
 #[derive(Debug, Copy, Clone)]
 enum Operator {
     Mul,
@@ -22,14 +27,43 @@ enum Operator {
     Concat,
 }
 
+const PART1_OPERATORS: &[Operator] = &[Operator::Add, Operator::Mul];
+const PART2_OPERATORS: &[Operator] = &[Operator::Add, Operator::Mul, Operator::Concat];
+
+impl Operator {
+    // None if the result overflows
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        match self {
+            Self::Add => left.checked_add(right),
+            Self::Mul => left.checked_mul(right),
+            Self::Concat => concat(left, right),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Input {
     result: u64,
     operands: Vec<u64>,
 }
 
-fn operator_permutations(operands_len: usize, with_concat: bool) -> Vec<Vec<Operator>> {
-    // TODO: could definitely memoize or make assumptions that it is the same for the run...
+type PermutationCache = HashMap<(usize, bool), Arc<Vec<Vec<Operator>>>>;
+
+// Every line with the same operand count tries the same set of operator
+// orderings, so build each set once and share it for the rest of the run.
+fn operator_permutations(operands_len: usize, with_concat: bool) -> Arc<Vec<Vec<Operator>>> {
+    static CACHE: OnceLock<Mutex<PermutationCache>> = OnceLock::new();
+    let mut cache = CACHE
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap();
+    cache
+        .entry((operands_len, with_concat))
+        .or_insert_with(|| Arc::new(build_operator_permutations(operands_len, with_concat)))
+        .clone()
+}
+
+fn build_operator_permutations(operands_len: usize, with_concat: bool) -> Vec<Vec<Operator>> {
     let mut permutations = Vec::new();
     if operands_len == 1 {
         return permutations; // base case
@@ -45,7 +79,7 @@ fn operator_permutations(operands_len: usize, with_concat: bool) -> Vec<Vec<Oper
     }
 
     // compute the next two combos and then recurse
-    let rem = operator_permutations(operands_len - 1, with_concat);
+    let rem = build_operator_permutations(operands_len - 1, with_concat);
     for perm in rem {
         let mut add_perm = Vec::from(&[Operator::Add]);
         let mut mul_perm = Vec::from(&[Operator::Mul]);
@@ -64,49 +98,76 @@ fn operator_permutations(operands_len: usize, with_concat: bool) -> Vec<Vec<Oper
     permutations
 }
 
+// The digits of `left` followed by the digits of `right` (e.g. 12 || 345 is
+// 12345), or None if that overflows.
+fn concat(left: u64, right: u64) -> Option<u64> {
+    let digits = right.checked_ilog10().unwrap_or(0) + 1;
+    left.checked_mul(10u64.checked_pow(digits)?)?
+        .checked_add(right)
+}
+
+#[derive(Debug, Default)]
+struct Evaluation {
+    /// Orderings of operators that produce the result
+    successful: Vec<Vec<Operator>>,
+
+    /// Orderings that were abandoned because an intermediate value overflowed
+    overflowed: usize,
+}
+
 impl Input {
-    fn compute_operators(&self, with_concat: bool) -> Vec<Vec<Operator>> {
+    // Whether any ordering of the operators makes the equation true; unlike
+    // `compute_operators` this stops at the first one found.
+    fn is_satisfiable(&self, operators: &[Operator]) -> bool {
+        // none of the operators make the running value smaller unless there
+        // is a zero to multiply by, so past the result is a dead end
+        let can_prune = !self.operands.contains(&0);
+        let mut stack = vec![(1, self.operands[0])];
+        while let Some((next, value)) = stack.pop() {
+            if next == self.operands.len() {
+                if value == self.result {
+                    return true;
+                }
+                continue;
+            }
+            if can_prune && value > self.result {
+                continue;
+            }
+            stack.extend(
+                operators
+                    .iter()
+                    .filter_map(|op| op.apply(value, self.operands[next]))
+                    .map(|value| (next + 1, value)),
+            );
+        }
+        false
+    }
+
+    fn compute_operators(&self, with_concat: bool) -> Evaluation {
         let mut successful: Vec<Vec<Operator>> = Vec::new();
-        //println!("");
-        'ordering: for op_ordering in operator_permutations(self.operands.len(), with_concat) {
+        let mut overflowed = 0;
+        let permutations = operator_permutations(self.operands.len(), with_concat);
+        'ordering: for op_ordering in permutations.iter() {
             let mut computed_res = self.operands[0];
-            // print!("{}: {computed_res}", self.result);
-            for (operand, operator) in self.operands[1..].iter().zip(&op_ordering) {
-                match operator {
-                    Operator::Add => {
-                        //print!(" + {operand}");
-                        computed_res = match computed_res.checked_add(*operand) {
-                            Some(res) => res,
-                            None => continue 'ordering, // overflow
-                        };
-                    }
-                    Operator::Mul => {
-                        //print!(" * {operand}");
-                        computed_res = match computed_res.checked_mul(*operand) {
-                            Some(res) => res,
-                            None => continue 'ordering, // overflow
-                        }
+            for (operand, operator) in self.operands[1..].iter().zip(op_ordering) {
+                computed_res = match operator.apply(computed_res, *operand) {
+                    Some(res) => res,
+                    None => {
+                        overflowed += 1;
+                        continue 'ordering;
                     }
-                    Operator::Concat => {
-                        let concatted_str = format!("{computed_res}{operand}");
-                        computed_res = match concatted_str.parse::<u64>() {
-                            Ok(v) => v,
-                            Err(e) => {
-                                eprintln!("{e} - {concatted_str:?}");
-                                continue 'ordering;
-                            } // overflow probably
-                        };
-                    }
-                }
+                };
             }
 
-            //println!(" = {computed_res}");
             if computed_res == self.result {
-                successful.push(op_ordering);
+                successful.push(op_ordering.clone());
             }
         }
 
-        successful
+        Evaluation {
+            successful,
+            overflowed,
+        }
     }
 }
 
@@ -134,27 +195,131 @@ fn parse_input<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Input>> {
     Ok(parsed_inputs)
 }
 
-fn main() -> anyhow::Result<()> {
-    let parsed_inputs = parse_input("d7-p1.txt")?;
-    let functional_res_sum: u64 = parsed_inputs
+// Sum the results of the equations that can be made true, reporting any
+// orderings skipped due to overflow along the way if verbose (which means
+// trying every ordering rather than stopping at the first that works).
+fn calibration_result(inputs: &[Input], with_concat: bool, verbose: bool) -> u64 {
+    let operators = if with_concat {
+        PART2_OPERATORS
+    } else {
+        PART1_OPERATORS
+    };
+    inputs
         .iter()
-        .filter(|i| i.compute_operators(false).len() > 0)
-        .map(|i| {
-            // println!("Good -> {i:?}");
-            i.result
+        .filter(|i| {
+            if !verbose {
+                return i.is_satisfiable(operators);
+            }
+            let evaluation = i.compute_operators(with_concat);
+            if evaluation.overflowed > 0 {
+                println!(
+                    "{}: {} ordering(s) overflowed, {} succeeded",
+                    i.result,
+                    evaluation.overflowed,
+                    evaluation.successful.len()
+                );
+            }
+            !evaluation.successful.is_empty()
         })
-        .sum();
-    println!("Part1 - Functional Sum: {functional_res_sum:?}");
-
-    let functional_res_sum: u64 = parsed_inputs
-        .iter()
-        .filter(|i| i.compute_operators(true).len() > 0)
         .map(|i| {
             // println!("Good -> {i:?}");
             i.result
         })
-        .sum();
-    println!("Part 2 - Functional Sum: {functional_res_sum:?}");
+        .sum()
+}
+
+// Same as `calibration_result` without the verbose reporting, but with the
+// equations checked in parallel; each one stands alone.
+fn calibration_result_parallel(inputs: &[Input], with_concat: bool) -> u64 {
+    let operators = if with_concat {
+        PART2_OPERATORS
+    } else {
+        PART1_OPERATORS
+    };
+    inputs
+        .par_iter()
+        .filter(|i| i.is_satisfiable(operators))
+        .map(|i| i.result)
+        .sum()
+}
+
+// Verbose reporting needs the full enumeration (and ordered output), so
+// only the plain search is run in parallel.
+fn solve(inputs: &[Input], with_concat: bool, verbose: bool) -> u64 {
+    if verbose {
+        calibration_result(inputs, with_concat, verbose)
+    } else {
+        calibration_result_parallel(inputs, with_concat)
+    }
+}
+
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d7-p1.txt")]
+    input: String,
+
+    /// Report equations where operator orderings were skipped due to overflow
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let parsed_inputs = parse_input(&cli.input)?;
+    let functional_res_sum = solve(&parsed_inputs, false, cli.verbose);
+    println!("Part 1: {functional_res_sum}");
+
+    let functional_res_sum = solve(&parsed_inputs, true, cli.verbose);
+    println!("Part 2: {functional_res_sum}");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_RESULTS: (u64, u64) = (3749, 11387);
+
+    // The early exit search (serial and parallel) and the full enumeration
+    // should agree with each other as well as with the puzzle's answers.
+    #[test]
+    fn example() {
+        let inputs = parse_input("d7-example1.txt").unwrap();
+        for (with_concat, expected) in [(false, EXAMPLE_RESULTS.0), (true, EXAMPLE_RESULTS.1)] {
+            for verbose in [false, true] {
+                assert_eq!(
+                    calibration_result(&inputs, with_concat, verbose),
+                    expected,
+                    "concat: {with_concat}, enumerating all: {verbose}"
+                );
+            }
+            assert_eq!(
+                calibration_result_parallel(&inputs, with_concat),
+                expected,
+                "concat: {with_concat}, parallel"
+            );
+        }
+    }
+
+    #[test]
+    fn concat_overflow() {
+        assert_eq!(concat(12, 345), Some(12345));
+        assert_eq!(concat(0, 0), Some(0));
+        assert_eq!(concat(u64::MAX / 10, 5), Some(u64::MAX));
+        assert_eq!(concat(u64::MAX / 10, 6), None);
+        assert_eq!(concat(10_000_000_000, 1_000_000_000), None);
+
+        // only the concatenation overflows here, so only that ordering is
+        // skipped and the other two are still tried
+        let input = Input {
+            result: 11_000_000_000,
+            operands: vec![10_000_000_000, 1_000_000_000],
+        };
+        for (with_concat, overflowed) in [(false, 0), (true, 1)] {
+            let evaluation = input.compute_operators(with_concat);
+            assert_eq!(evaluation.overflowed, overflowed, "concat: {with_concat}");
+            assert_eq!(evaluation.successful.len(), 1, "concat: {with_concat}");
+        }
+    }
+}
@@ -5,6 +5,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use clap::Parser;
 use itertools::Itertools;
 
 #[derive(Debug, Clone)]
@@ -45,150 +46,102 @@ fn ant_positions(inputs: &Vec<Vec<AntMapPosition>>) -> HashMap<char, Vec<(usize,
     antennas_with_positions
 }
 
+// NOTE: positions throughout are (row, col) tuples, matching the order
+// in which they are found in `ant_positions`.
 fn in_bounds(
-    x: Option<usize>,
-    y: Option<usize>,
+    row: Option<usize>,
+    col: Option<usize>,
     row_count: usize,
     col_count: usize,
 ) -> Option<(usize, usize)> {
-    match (x, y) {
-        (Some(x), Some(y)) if x < row_count && y < col_count => Some((x, y)),
+    match (row, col) {
+        (Some(row), Some(col)) if row < row_count && col < col_count => Some((row, col)),
         _ => None,
     }
 }
 
-fn insert_resonant_ext(
-    output: &mut Vec<(usize, usize)>,
-    a: (Option<usize>, Option<usize>),
-    b: (Option<usize>, Option<usize>),
-    a_deltas: (isize, isize),
-    b_deltas: (isize, isize),
+// One step of `delta` from `pos`, if that is still on the map.
+fn step(
+    pos: (usize, usize),
+    delta: (isize, isize),
     row_count: usize,
     col_count: usize,
-) {
-    println!("insert_resonant_ext: a={a:?}, b={b:?}");
-    let a_val = in_bounds(a.0, a.1, row_count, col_count);
-    let b_val = in_bounds(b.0, b.1, row_count, col_count);
-    if a_val.is_none() && b_val.is_none() {
-        return; // we're done done
-    }
-
-    let aprime = if let Some(a) = a_val {
-        output.push(a);
-        (
-            a.0.checked_add_signed(a_deltas.0),
-            a.1.checked_add_signed(a_deltas.1),
-        )
-    } else {
-        (None, None)
-    };
-
-    let bprime = if let Some(b) = b_val {
-        output.push(b);
-        (
-            b.0.checked_add_signed(b_deltas.0),
-            b.1.checked_add_signed(b_deltas.1),
-        )
-    } else {
-        (None, None)
-    };
-
-    // recurse to carry on the resonance
-    insert_resonant_ext(
-        output, aprime, bprime, a_deltas, b_deltas, row_count, col_count,
+) -> Option<(usize, usize)> {
+    in_bounds(
+        pos.0.checked_add_signed(delta.0),
+        pos.1.checked_add_signed(delta.1),
+        row_count,
+        col_count,
     )
 }
 
+// Antinodes for the pair of antennas `a` and `b` lie on the line through
+// them, beyond a (stepping by -delta) and beyond b (stepping by +delta).
+// Without harmonics there is just one on each side; with them, every step
+// along the line counts, starting from the antennas themselves.
 fn insert_resonant(
     output: &mut Vec<(usize, usize)>,
     a: (usize, usize),
     b: (usize, usize),
     row_count: usize,
     col_count: usize,
+    harmonics: bool,
 ) {
-    let (a_x, a_y, b_x, b_y) = (a.0 as isize, a.1 as isize, b.0 as isize, b.1 as isize);
-    let delta_x = a_x.abs_diff(b_x) as isize;
-    let delta_y = a_y.abs_diff(b_y) as isize;
-    let (a_deltas, b_deltas) = match (a.0 > b.0, a.1 > b.1) {
-        (true, true) => {
-            // a is to the right and below b:
-            // ---
-            // b
-            //   a
-            (
-                (delta_x, delta_y), // bottom right
-                (-delta_x, -delta_y),
-            ) // top left
-        }
-        (true, false) => {
-            // a is to the right and above b:
-            // ---
-            //   a
-            // b
-            ((delta_x, -delta_y), (-delta_x, delta_y))
-        }
-        (false, true) => {
-            // a is to the left and below b
-            // ---
-            //   b
-            // a
-            ((-delta_x, delta_y), (delta_x, -delta_y))
-        }
-        (false, false) => {
-            // a is to the left and above b
-            // ---
-            // a
-            //   b
-            ((-delta_x, -delta_y), (delta_x, delta_y))
+    let delta = (b.0 as isize - a.0 as isize, b.1 as isize - a.1 as isize);
+    for (antenna, delta) in [(a, (-delta.0, -delta.1)), (b, delta)] {
+        if harmonics {
+            let mut next = Some(antenna);
+            while let Some(pos) = next {
+                output.push(pos);
+                next = step(pos, delta, row_count, col_count);
+            }
+        } else {
+            output.extend(step(antenna, delta, row_count, col_count));
         }
-    };
-    insert_resonant_ext(
-        output,
-        (Some(a.0), Some(a.1)),
-        (Some(b.0), Some(b.1)),
-        a_deltas,
-        b_deltas,
-        row_count,
-        col_count,
-    )
+    }
 }
 
 fn compute_antinodes(
     ant_positions: &Vec<(usize, usize)>,
     row_count: usize,
     col_count: usize,
+    harmonics: bool,
 ) -> Vec<(usize, usize)> {
     let mut antinode_positions = Vec::new();
-    for ((a_x, a_y), (b_x, b_y)) in ant_positions.iter().tuple_combinations() {
+    for ((a_row, a_col), (b_row, b_col)) in ant_positions.iter().tuple_combinations() {
         insert_resonant(
             &mut antinode_positions,
-            (*a_x, *a_y),
-            (*b_x, *b_y),
+            (*a_row, *a_col),
+            (*b_row, *b_col),
             row_count,
             col_count,
+            harmonics,
         );
     }
 
-    println!("ANTINODE POSITIONS: {antinode_positions:?}");
     antinode_positions
 }
 
-fn main() -> anyhow::Result<()> {
-    let inputs = parse_input("d8-p1.txt")?;
-    let ant_positions = ant_positions(&inputs);
+// Every distinct antinode position across all of the frequencies.
+fn all_antinodes(inputs: &Vec<Vec<AntMapPosition>>, harmonics: bool) -> HashSet<(usize, usize)> {
+    let row_count = inputs.len();
+    let col_count = inputs.first().map(|row| row.len()).unwrap_or(0);
+    ant_positions(inputs)
+        .values()
+        .flat_map(|positions| compute_antinodes(positions, row_count, col_count, harmonics))
+        .collect()
+}
+
+// Draw the antennas for each frequency and their antinodes (with harmonics),
+// marking antennas that are also antinodes with '*'.
+fn print_frequencies(inputs: &Vec<Vec<AntMapPosition>>) {
     let row_count = inputs.len();
     let col_count = inputs[0].len();
-    let mut unique_antinodes: HashSet<(usize, usize)> = HashSet::new();
-    for (ant, positions) in ant_positions.iter() {
+    for (ant, positions) in ant_positions(inputs).iter().sorted() {
         println!("Antenna '{ant}' @ ({positions:?})");
-        let anti_positions = compute_antinodes(positions, row_count, col_count);
-
-        for anti_pos in anti_positions.iter() {
-            unique_antinodes.insert(*anti_pos);
-        }
-
-        for row in 0..inputs.len() {
-            for col in 0..inputs[0].len() {
+        let anti_positions = compute_antinodes(positions, row_count, col_count, true);
+        for row in 0..row_count {
+            for col in 0..col_count {
                 let in_positions = positions.contains(&(row, col));
                 let in_anti = anti_positions.contains(&(row, col));
                 let c = match (in_positions, in_anti) {
@@ -203,8 +156,72 @@ fn main() -> anyhow::Result<()> {
         }
         println!("");
     }
+}
 
-    println!("Unique Antinode Positions: {}", unique_antinodes.len());
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d8-p1.txt")]
+    input: String,
+
+    /// Draw the antennas and antinodes (with harmonics) for each frequency
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let inputs = parse_input(&cli.input)?;
+    if cli.verbose {
+        print_frequencies(&inputs);
+    }
+    println!("Part 1: {}", all_antinodes(&inputs, false).len());
+    println!("Part 2: {}", all_antinodes(&inputs, true).len());
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example() {
+        let inputs = parse_input("d8-example1.txt").unwrap();
+        assert_eq!(all_antinodes(&inputs, false).len(), 14);
+        assert_eq!(all_antinodes(&inputs, true).len(), 34);
+    }
+
+    // 10 columns by 6 rows.  One antinode of the a's, at (row, col) (7, 3),
+    // is off the bottom of the map and one of the b's, at (0, 9), is on its
+    // right edge, so mixing up the row and column counts gets both wrong.
+    const WIDE_MAP: &str = "\
+.......bb.
+..........
+..........
+.a........
+..........
+..a.......";
+
+    #[test]
+    fn wide_map() {
+        let inputs: Vec<Vec<AntMapPosition>> = WIDE_MAP
+            .lines()
+            .map(|l| {
+                l.chars()
+                    .map(|c| match c {
+                        '.' => AntMapPosition::Blank,
+                        c => AntMapPosition::Antenna(c),
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            all_antinodes(&inputs, false),
+            HashSet::from([(1, 0), (0, 6), (0, 9)])
+        );
+
+        let mut expected: HashSet<(usize, usize)> = (0..10).map(|col| (0, col)).collect();
+        expected.extend([(1, 0), (3, 1), (5, 2)]);
+        assert_eq!(all_antinodes(&inputs, true), expected);
+    }
+}
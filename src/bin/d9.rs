@@ -1,8 +1,12 @@
 use std::{
     collections::VecDeque,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
+use aoc::Part;
+use clap::Parser;
+
 #[derive(Debug, Clone)]
 struct AllocatedBlocks {
     disk_offset: usize,
@@ -16,6 +20,139 @@ struct FreeBlocks {
     length: usize,
 }
 
+/// A run of blocks on the disk belonging to a single file (or free if `id`
+/// is None).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    id: Option<usize>,
+    offset: usize,
+    length: usize,
+}
+
+/// A single block of a disk layout, as far as the checksum is concerned:
+/// either a file id, or nothing for free space.
+pub trait Block {
+    fn file_id(&self) -> Option<usize>;
+}
+
+/// Dense layouts have a file in every block.
+impl Block for usize {
+    fn file_id(&self) -> Option<usize> {
+        Some(*self)
+    }
+}
+
+/// Gapped layouts use `None` for free blocks.
+impl Block for Option<usize> {
+    fn file_id(&self) -> Option<usize> {
+        *self
+    }
+}
+
+/// The filesystem checksum of a layout built however you like: the sum of
+/// position * file id over every block, where free blocks contribute
+/// nothing.  A free block therefore counts the same as one holding file 0.
+pub fn checksum<B: Block>(blocks: &[B]) -> usize {
+    blocks
+        .iter()
+        .enumerate()
+        .map(|(position, block)| position * block.file_id().unwrap_or(0))
+        .sum()
+}
+
+/// A disk laid out as an ordered list of contiguous segments, so we never
+/// need to hold every block in memory.
+#[derive(Debug, Clone, Default)]
+pub struct Disk {
+    segments: Vec<Segment>,
+}
+
+impl Disk {
+    // Total size of the disk in blocks
+    fn len(&self) -> usize {
+        self.segments
+            .last()
+            .map(|segment| segment.offset + segment.length)
+            .unwrap_or(0)
+    }
+
+    // Append `length` blocks for `id` to the end of the disk, extending the
+    // last segment if it is for the same id
+    fn push(&mut self, id: Option<usize>, length: usize) {
+        if length == 0 {
+            return;
+        }
+        match self.segments.last_mut() {
+            Some(last) if last.id == id => last.length += length,
+            _ => {
+                let offset = self.len();
+                self.segments.push(Segment { id, offset, length });
+            }
+        }
+    }
+
+    // Pad the disk with free blocks up to `offset`
+    fn pad_to(&mut self, offset: usize) {
+        self.push(None, offset.saturating_sub(self.len()));
+    }
+
+    fn iter_blocks(&self) -> impl Iterator<Item = Option<usize>> + '_ {
+        self.segments
+            .iter()
+            .flat_map(|segment| itertools::repeat_n(segment.id, segment.length))
+    }
+
+    /// Same as `checksum` over the blocks, without expanding them; each
+    /// file segment contributes id * (offset + ... + offset + length - 1).
+    pub fn checksum(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| {
+                let Segment { id, offset, length } = *segment;
+                id.unwrap_or(0) * (offset * length + length * length.saturating_sub(1) / 2)
+            })
+            .sum()
+    }
+
+    // Free the segment at `index`, merging it with any free space either side
+    fn free(&mut self, index: usize) {
+        self.segments[index].id = None;
+        if self
+            .segments
+            .get(index + 1)
+            .is_some_and(|next| next.id.is_none())
+        {
+            let next = self.segments.remove(index + 1);
+            self.segments[index].length += next.length;
+        }
+        if index > 0 && self.segments[index - 1].id.is_none() {
+            let freed = self.segments.remove(index);
+            self.segments[index - 1].length += freed.length;
+        }
+    }
+
+    // Put `length` blocks of file `id` at the start of the free segment at
+    // `index`, which must be at least that long; whatever is left over stays
+    // free after it
+    fn allocate(&mut self, index: usize, id: usize, length: usize) {
+        let free = self.segments[index];
+        debug_assert!(free.id.is_none() && free.length >= length);
+        self.segments[index] = Segment {
+            id: Some(id),
+            offset: free.offset,
+            length,
+        };
+        if free.length > length {
+            let rest = Segment {
+                id: None,
+                offset: free.offset + length,
+                length: free.length - length,
+            };
+            self.segments.insert(index + 1, rest);
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct DiskMap {
     // the raw alternating fs blocks / free
@@ -47,112 +184,437 @@ impl DiskMap {
         }
         DiskMap { allocs, free_list }
     }
+
+    // The uncompacted layout of the disk
+    fn layout(&self) -> Disk {
+        let mut disk = Disk::default();
+        for alloc in self.allocs.iter() {
+            disk.pad_to(alloc.disk_offset);
+            disk.push(Some(alloc.id), alloc.length);
+        }
+        let end = self
+            .free_list
+            .iter()
+            .map(|free| free.disk_offset + free.length)
+            .max()
+            .unwrap_or(0);
+        disk.pad_to(end);
+        disk
+    }
+}
+
+impl FromStr for DiskMap {
+    type Err = anyhow::Error;
+
+    // Parse the compact form, e.g. "2333133121414131402"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            anyhow::bail!("Disk map is empty");
+        }
+
+        let raw = s
+            .trim()
+            .chars()
+            .enumerate()
+            .map(|(idx, c)| match c.to_digit(10) {
+                Some(d) => Ok(d as u8),
+                None => Err(anyhow::anyhow!(
+                    "Unexpected char in diskmap at {idx}: {c:?}"
+                )),
+            })
+            .collect::<anyhow::Result<Vec<u8>>>()?;
+        Ok(DiskMap::from_raw(raw))
+    }
+}
+
+// Render the disk in the same style as the puzzle description, with '.' for
+// free blocks; ids that aren't a single digit are wrapped in brackets.
+fn render(disk: &Disk) -> String {
+    disk.iter_blocks()
+        .map(|block| match block {
+            None => ".".to_string(),
+            Some(id) if id < 10 => id.to_string(),
+            Some(id) => format!("[{id}]"),
+        })
+        .collect()
+}
+
+// Parse a layout in the form `render` produces, e.g. "00...111...2" or
+// "0.[10][10]", into blocks with `None` for the free ones.
+fn parse_layout(s: &str) -> anyhow::Result<Vec<Option<usize>>> {
+    let mut blocks = Vec::new();
+    let mut chars = s.trim().chars();
+    while let Some(c) = chars.next() {
+        let block = match c {
+            '.' => None,
+            '[' => {
+                let id: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                Some(id.parse()?)
+            }
+            c => match c.to_digit(10) {
+                Some(d) => Some(d as usize),
+                None => anyhow::bail!("Unexpected char in layout: {c:?}"),
+            },
+        };
+        blocks.push(block);
+    }
+    Ok(blocks)
 }
 
 fn parse_diskmap<P: AsRef<Path>>(path: P) -> anyhow::Result<DiskMap> {
     let full_path = PathBuf::from(".").join("inputs").join(path);
     let contents = std::fs::read_to_string(full_path)?;
-    let trimmed = contents.trim();
-    let diskmap_raw = trimmed
-        .bytes()
-        .map(|c| {
-            if c < b'0' || c > b'9' {
-                panic!("Unexpected char in diskmap: '{c:?}'");
-            }
-            c - b'0'
-        })
-        .collect();
-    Ok(DiskMap::from_raw(diskmap_raw))
+    contents.parse()
 }
 
-fn compact_disk(diskmap: &DiskMap) -> Vec<usize> {
-    let mut allocs = VecDeque::from_iter(diskmap.allocs.iter().cloned());
-    let mut frees = VecDeque::from_iter(diskmap.free_list.iter().cloned());
+fn compact_disk(disk: &Disk) -> Disk {
+    let (files, frees): (Vec<Segment>, Vec<Segment>) = disk
+        .segments
+        .iter()
+        .partition(|segment| segment.id.is_some());
+    let mut files = VecDeque::from(files);
+    let mut frees = VecDeque::from(frees);
 
-    let mut compacted = Vec::new();
-    while !frees.is_empty() && !allocs.is_empty() {
+    let mut compacted = Disk::default();
+    while !frees.is_empty() && !files.is_empty() {
         let idx = compacted.len();
-        if allocs[0].disk_offset == idx {
-            let alloc = allocs.pop_front().unwrap();
-            compacted.extend(itertools::repeat_n(alloc.id, alloc.length));
+        if files[0].offset == idx {
+            let file = files.pop_front().unwrap();
+            compacted.push(file.id, file.length);
             continue;
         }
 
-        // fill free space
-        let alloc = allocs.back_mut().unwrap();
+        // fill as much of the free space as we can from the last file
+        let file = files.back_mut().unwrap();
         let free = frees.front_mut().unwrap();
-        while alloc.length > 0 && free.length > 0 {
-            compacted.push(alloc.id);
-            alloc.length -= 1;
-            free.length -= 1;
-        }
-        if alloc.length == 0 {
-            allocs.pop_back();
+        let moved = file.length.min(free.length);
+        compacted.push(file.id, moved);
+        file.length -= moved;
+        free.length -= moved;
+        if file.length == 0 {
+            files.pop_back();
         }
         if free.length == 0 {
             frees.pop_front();
         }
     }
 
+    // once the free space runs out, whatever is left is already packed
+    for file in files {
+        compacted.push(file.id, file.length);
+    }
+
     compacted
 }
 
-fn defrag_disk(diskmap: &DiskMap) -> Vec<usize> {
-    let mut allocs = VecDeque::from_iter(diskmap.allocs.iter().cloned());
-    let mut defragged_allocs: Vec<AllocatedBlocks> = Vec::new();
-    let mut frees = VecDeque::from_iter(diskmap.free_list.iter().cloned());
+// Move each whole file, highest id first, into the first free segment to its
+// left that it fits in, if there is one.
+fn defrag_disk(disk: &Disk) -> Disk {
+    let mut disk = disk.clone();
+    let max_id = disk.segments.iter().filter_map(|segment| segment.id).max();
+    for id in (0..=max_id.unwrap_or(0)).rev() {
+        let Some(file) = disk.segments.iter().rposition(|s| s.id == Some(id)) else {
+            continue;
+        };
+        let length = disk.segments[file].length;
+        let Some(gap) = disk.segments[..file]
+            .iter()
+            .position(|s| s.id.is_none() && s.length >= length)
+        else {
+            continue; // there's no free space earlier on the disk big enough; it stays put
+        };
+        disk.free(file);
+        disk.allocate(gap, id, length);
+    }
+    disk
+}
+
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(short, long, default_value = "d9-p1.txt")]
+    input: String,
 
-    // the first alloc is already defragged
-    defragged_allocs.push(allocs.pop_front().unwrap());
+    /// Use this compact disk map (e.g. 2333133121414131402) instead of --input
+    #[arg(long)]
+    disk_map: Option<String>,
 
-    'alloc: while let Some(alloc) = allocs.pop_back() {
-        for free in frees.iter_mut() {
-            if free.disk_offset > alloc.disk_offset {
-                defragged_allocs.push(alloc);
-                continue 'alloc;
-            }
+    #[arg(short, long, value_enum, default_value_t)]
+    part: Part,
 
-            if free.disk_offset < alloc.disk_offset && free.length >= alloc.length {
-                free.length -= alloc.length; // note: we're just leaving empties in the free list :shruggie:
-                defragged_allocs.push(AllocatedBlocks {
-                    disk_offset: free.disk_offset,
-                    id: alloc.id,
-                    length: alloc.length,
-                });
+    /// Render the disk layout before and after compaction/defragmentation
+    #[arg(short, long, action)]
+    verbose: bool,
+
+    /// Print the checksum of this layout (e.g. 0099811188827773336446555566,
+    /// '.' for free blocks and [id] for ids over 9) instead of solving
+    #[arg(long)]
+    checksum_layout: Option<String>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    if let Some(layout) = &cli.checksum_layout {
+        println!("Checksum: {}", checksum(&parse_layout(layout)?));
+        return Ok(());
+    }
+    let diskmap = match &cli.disk_map {
+        Some(raw) => raw.parse()?,
+        None => parse_diskmap(&cli.input)?,
+    };
+    let disk = diskmap.layout();
+    if cli.verbose {
+        println!("Initial:    {}", render(&disk));
+    }
+
+    if cli.part.part1() {
+        let compacted = compact_disk(&disk);
+        if cli.verbose {
+            println!("Compacted:  {}", render(&compacted));
+        }
+        println!("Part 1: {}", compacted.checksum());
+    }
+
+    if cli.part.part2() {
+        let defragged = defrag_disk(&disk);
+        if cli.verbose {
+            println!("Defragged:  {}", render(&defragged));
+        }
+        println!("Part 2: {}", defragged.checksum());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Disk maps where compaction ends right at a boundary, with the compacted
+    // layout and checksum.  In "122" the last file exactly fills the last free
+    // space; in "123" and "12345" the last file is only partly moved and the
+    // rest stays where it was.
+    const COMPACTION_CASES: [(&str, &str, usize); 5] = [
+        ("1", "0", 0),
+        ("122", "011", 3),
+        ("123", "0111", 6),
+        ("12345", "022111222", 60),
+        ("2333133121414131402", "0099811188827773336446555566", 1928),
+    ];
+
+    // The checksum of the dense and gapped forms of the same layout should
+    // agree when the gaps are filled in with file 0, for both the compacted
+    // (no gaps) and defragmented (gaps) example, and match the segment version.
+    const EXAMPLE_CHECKSUMS: [(&str, usize); 2] = [
+        ("0099811188827773336446555566..............", 1928),
+        ("00992111777.44.333....5555.6666.....8888..", 2858),
+    ];
+
+    // The puzzle's own description of compaction, a block at a time: move the
+    // last file block into the first free block until there are no gaps.  Far
+    // slower than `compact_disk`, but hard to get wrong.
+    fn compact_blocks(diskmap: &DiskMap) -> Disk {
+        let mut blocks: Vec<Option<usize>> = diskmap.layout().iter_blocks().collect();
+        let (mut free, mut last) = (0, blocks.len());
+        loop {
+            while free < blocks.len() && blocks[free].is_some() {
+                free += 1;
+            }
+            while last > 0 && blocks[last - 1].is_none() {
+                last -= 1;
+            }
+            if last == 0 || free >= last - 1 {
                 break;
             }
+            blocks.swap(free, last - 1);
         }
+
+        let mut disk = Disk::default();
+        for id in blocks.into_iter().take_while(|id| id.is_some()) {
+            disk.push(id, 1);
+        }
+        disk
     }
 
-    defragged_allocs.sort_by_key(|block| block.disk_offset);
+    #[test]
+    fn compaction_boundaries() {
+        for (raw, layout, checksum) in COMPACTION_CASES {
+            let diskmap: DiskMap = raw.parse().unwrap();
+            let compacted = compact_disk(&diskmap.layout());
+            assert_eq!(render(&compacted), layout, "{raw}");
+            assert_eq!(compacted.checksum(), checksum, "{raw}");
+            assert_eq!(
+                compacted.segments,
+                compact_blocks(&diskmap).segments,
+                "{raw}"
+            );
+        }
+    }
 
-    let mut defragged_disk: Vec<usize> = Vec::new();
-    for alloc in defragged_allocs {
-        // we've got a gap; we could look at the free list but we don't need to
-        while defragged_disk.len() < alloc.disk_offset {
-            defragged_disk.push(0);
+    #[test]
+    fn dense_and_gapped_checksums() {
+        for (layout, expected) in EXAMPLE_CHECKSUMS {
+            let gapped = parse_layout(layout).unwrap();
+            let dense: Vec<usize> = gapped.iter().map(|id| id.unwrap_or(0)).collect();
+            assert_eq!(checksum(&gapped), expected, "{layout} (gapped)");
+            assert_eq!(checksum(&dense), expected, "{layout} (dense)");
         }
 
-        (0..alloc.length).for_each(|_| defragged_disk.push(alloc.id))
+        let diskmap: DiskMap = COMPACTION_CASES[COMPACTION_CASES.len() - 1]
+            .0
+            .parse()
+            .unwrap();
+        let disk = diskmap.layout();
+        for (disk, (layout, expected)) in [compact_disk(&disk), defrag_disk(&disk)]
+            .iter()
+            .zip(EXAMPLE_CHECKSUMS)
+        {
+            assert_eq!(disk.checksum(), expected, "{layout} (segments)");
+        }
     }
 
-    defragged_disk
-}
+    // Expanding the segments into blocks should give the same checksum as the
+    // segment arithmetic, before and after moving files around.
+    #[test]
+    fn segment_checksums() {
+        for (raw, _, _) in COMPACTION_CASES {
+            let disk = raw.parse::<DiskMap>().unwrap().layout();
+            for disk in [compact_disk(&disk), defrag_disk(&disk), disk] {
+                let blocks: Vec<Option<usize>> = disk.iter_blocks().collect();
+                assert_eq!(
+                    disk.checksum(),
+                    checksum(&blocks),
+                    "{raw}: {}",
+                    render(&disk)
+                );
+            }
+        }
+    }
 
-fn checksum(disk: &[usize]) -> usize {
-    disk.iter().enumerate().map(|(i, id)| i * *id).sum()
-}
+    // The example from the puzzle, drawn the same way the puzzle does
+    #[test]
+    fn render_example() {
+        let disk = COMPACTION_CASES[COMPACTION_CASES.len() - 1]
+            .0
+            .parse::<DiskMap>()
+            .unwrap()
+            .layout();
+        assert_eq!(render(&disk), "00...111...2...333.44.5555.6666.777.888899");
+        assert_eq!(render(&defrag_disk(&disk)), EXAMPLE_CHECKSUMS[1].0);
+    }
 
-fn main() -> anyhow::Result<()> {
-    let diskmap = parse_diskmap("d9-p1.txt")?;
-    // println!("diskmap: {:?}", diskmap);
-    let compacted = compact_disk(&diskmap);
-    // println!("Compacted: {compacted:?}");
-    println!("Checksum Compacted: {}", checksum(&compacted));
+    #[test]
+    fn example_offsets() {
+        let diskmap: DiskMap = COMPACTION_CASES[COMPACTION_CASES.len() - 1]
+            .0
+            .parse()
+            .unwrap();
+        let allocs: Vec<(usize, usize)> = diskmap
+            .allocs
+            .iter()
+            .map(|alloc| (alloc.disk_offset, alloc.length))
+            .collect();
+        let frees: Vec<(usize, usize)> = diskmap
+            .free_list
+            .iter()
+            .map(|free| (free.disk_offset, free.length))
+            .collect();
+        assert_eq!(
+            allocs,
+            [
+                (0, 2),
+                (5, 3),
+                (11, 1),
+                (15, 3),
+                (19, 2),
+                (22, 4),
+                (27, 4),
+                (32, 3),
+                (36, 4),
+                (40, 2)
+            ]
+        );
+        assert_eq!(
+            frees,
+            [
+                (2, 3),
+                (8, 3),
+                (12, 3),
+                (18, 1),
+                (21, 1),
+                (26, 1),
+                (31, 1),
+                (35, 1),
+                (40, 0)
+            ]
+        );
+    }
 
-    let defragged = defrag_disk(&diskmap);
-    // println!("Defragged: {defragged:?}");
-    println!("Checksum Defragged: {}", checksum(&defragged));
+    // Freeing file 4 merges it with the free block either side; putting it
+    // back at the start of that gap leaves the rest of the gap after it.
+    #[test]
+    fn free_and_reallocate() {
+        let mut disk = COMPACTION_CASES[COMPACTION_CASES.len() - 1]
+            .0
+            .parse::<DiskMap>()
+            .unwrap()
+            .layout();
+        let file = disk.segments.iter().position(|s| s.id == Some(4)).unwrap();
+        let segments = disk.segments.len();
 
-    Ok(())
+        disk.free(file);
+        let gap = file - 1;
+        assert_eq!(disk.segments.len(), segments - 2);
+        assert_eq!(
+            disk.segments[gap],
+            Segment {
+                id: None,
+                offset: 18,
+                length: 4
+            }
+        );
+
+        disk.allocate(gap, 4, 2);
+        assert_eq!(disk.segments.len(), segments - 1);
+        assert_eq!(
+            disk.segments[gap..gap + 3],
+            [
+                Segment {
+                    id: Some(4),
+                    offset: 18,
+                    length: 2
+                },
+                Segment {
+                    id: None,
+                    offset: 20,
+                    length: 2
+                },
+                Segment {
+                    id: Some(5),
+                    offset: 22,
+                    length: 4
+                },
+            ]
+        );
+        assert_eq!(render(&disk), "00...111...2...33344..5555.6666.777.888899");
+    }
+
+    #[test]
+    fn empty_map() {
+        for raw in ["", "\n"] {
+            let err = raw.parse::<DiskMap>().unwrap_err();
+            assert_eq!(err.to_string(), "Disk map is empty", "{raw:?}");
+        }
+    }
+
+    // An even number of digits means the map ends on free space, which is
+    // kept in the layout but never has anything to move into it.
+    #[test]
+    fn trailing_free_space() {
+        for (raw, layout, compacted) in [("12", "0..", "0"), ("1232", "0..111..", "0111")] {
+            let disk = raw.parse::<DiskMap>().unwrap().layout();
+            assert_eq!(render(&disk), layout, "{raw}");
+            assert_eq!(render(&compact_disk(&disk)), compacted, "{raw}");
+            assert_eq!(render(&defrag_disk(&disk)), layout, "{raw}");
+        }
+    }
 }
@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+
+use crate::Part;
+
+// Flags shared between the days; flatten into a day's own `Cli` with
+// `#[command(flatten)]` so the common flags have the same names everywhere.
+// (Not a doc comment, as clap would use it as the help text of every day.)
+#[derive(Debug, Clone, clap::Args)]
+pub struct CommonArgs {
+    /// Input file in inputs/ (defaults to the day's puzzle input)
+    #[arg(short, long)]
+    pub input: Option<String>,
+
+    #[arg(short, long, value_enum, default_value_t)]
+    pub part: Part,
+
+    /// Print more detail while solving, where the day supports it
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Report how long each part takes to solve
+    #[arg(long)]
+    pub timing: bool,
+
+    /// Animate the solution, where the day supports it
+    #[arg(short, long)]
+    pub animate: bool,
+
+    /// Delay between frames of the animation
+    #[arg(long, default_value_t = 100)]
+    pub delay_ms: u64,
+}
+
+impl CommonArgs {
+    /// The input given on the command line, or the day's default.
+    pub fn input_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.input.as_deref().unwrap_or(default)
+    }
+
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+
+    /// Run `f`, reporting how long it took under `label` if timing was
+    /// requested.
+    pub fn timed<T>(&self, label: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        if self.timing {
+            println!("{label} took {:.2?}", start.elapsed());
+        }
+        result
+    }
+}
@@ -0,0 +1,128 @@
+/// One of the four orthogonal directions on a grid, with up being towards
+/// row 0.
+#[cfg_attr(feature = "enumset", derive(enumset::EnumSetType))]
+#[cfg_attr(not(feature = "enumset"), derive(Clone, Copy, PartialEq, Eq))]
+#[derive(Debug, Hash, PartialOrd, Ord)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// A compact set of directions, e.g. every way a cell has been crossed.
+#[cfg(feature = "enumset")]
+pub type DirectionSet = enumset::EnumSet<Direction>;
+
+impl TryFrom<char> for Direction {
+    type Error = String;
+
+    /// The direction drawn as `value` by `as_char`.
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        Direction::ALL
+            .into_iter()
+            .find(|dir| dir.as_char() == value)
+            .ok_or_else(|| format!("'{value}' not an expected direction input"))
+    }
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    /// Position of this direction in `ALL`, for indexing per-direction arrays.
+    pub fn idx(&self) -> usize {
+        *self as usize
+    }
+
+    /// The arrow used to draw something heading this way.
+    pub fn as_char(&self) -> char {
+        match self {
+            Self::Up => '^',
+            Self::Down => 'v',
+            Self::Left => '<',
+            Self::Right => '>',
+        }
+    }
+
+    /// (x, y) offset of a single step this way.
+    pub fn dx_dy(&self) -> (isize, isize) {
+        match self {
+            Self::Up => (0, -1),
+            Self::Down => (0, 1),
+            Self::Left => (-1, 0),
+            Self::Right => (1, 0),
+        }
+    }
+
+    pub fn opposite_direction(&self) -> Direction {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
+
+    /// Number of quarter turns needed to go from facing this way to facing
+    /// `other`.
+    pub fn turns_to_face(&self, other: Direction) -> usize {
+        if self == &other {
+            0
+        } else if self.opposite_direction() == other {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns() {
+        for dir in Direction::ALL {
+            assert_eq!(Direction::ALL[dir.idx()], dir);
+            assert_eq!(dir.opposite_direction().opposite_direction(), dir);
+            assert_eq!(dir.turns_to_face(dir), 0);
+            assert_eq!(dir.turns_to_face(dir.opposite_direction()), 2);
+            let (dx, dy) = dir.dx_dy();
+            assert_eq!(dir.opposite_direction().dx_dy(), (-dx, -dy));
+        }
+        assert_eq!(Direction::Up.turns_to_face(Direction::Left), 1);
+    }
+
+    #[test]
+    fn chars() {
+        for dir in Direction::ALL {
+            assert_eq!(Direction::try_from(dir.as_char()), Ok(dir));
+        }
+        assert!(Direction::try_from('.').is_err());
+    }
+
+    #[cfg(feature = "enumset")]
+    #[test]
+    fn direction_set_round_trip() {
+        let mut set = DirectionSet::new();
+        assert_eq!(set.len(), 0);
+        for (count, dir) in Direction::ALL.into_iter().enumerate() {
+            assert!(set.insert(dir));
+            assert!(!set.insert(dir), "{dir:?} inserted twice");
+            assert_eq!(set.len(), count + 1);
+        }
+        assert_eq!(set.iter().collect::<Vec<_>>(), Direction::ALL);
+
+        for dir in Direction::ALL {
+            let only = DirectionSet::only(dir);
+            assert_eq!(only.len(), 1);
+            assert_eq!(only.iter().collect::<Vec<_>>(), [dir]);
+            assert!(set.is_superset(only));
+        }
+    }
+}
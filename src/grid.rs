@@ -0,0 +1,242 @@
+use std::ops::{Index, IndexMut};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Point {
+    pub fn new(x: usize, y: usize) -> Self {
+        Point { x, y }
+    }
+}
+
+/// Wrap a coordinate onto a toroidal axis of the given size; unlike `%`
+/// this is never negative, so `wrap(-1, 10) == 9`.
+pub fn wrap(value: isize, modulus: isize) -> isize {
+    value.rem_euclid(modulus)
+}
+
+/// A dense, row-major 2d grid of cells addressed by `Point { x: col, y: row }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T> Grid<T> {
+    /// Build a grid from rows; panics if the rows are ragged.
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Self {
+        let height = rows.len();
+        let width = rows.first().map(|r| r.len()).unwrap_or(0);
+        let mut cells = Vec::with_capacity(width * height);
+        for (y, row) in rows.into_iter().enumerate() {
+            if row.len() != width {
+                panic!("Row {y} has {} cells, expected {width}", row.len());
+            }
+            cells.extend(row);
+        }
+        Grid {
+            cells,
+            width,
+            height,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn in_bounds(&self, point: Point) -> bool {
+        point.x < self.width && point.y < self.height
+    }
+
+    pub fn get(&self, point: Point) -> Option<&T> {
+        if self.in_bounds(point) {
+            Some(&self.cells[point.y * self.width + point.x])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, point: Point) -> Option<&mut T> {
+        if self.in_bounds(point) {
+            Some(&mut self.cells[point.y * self.width + point.x])
+        } else {
+            None
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> + '_ {
+        // guard against chunks(0) on an empty grid
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| (Point::new(idx % self.width, idx / self.width), item))
+    }
+
+    /// Both diagonal families: first every top-left to bottom-right diagonal
+    /// (starting from the bottom-left corner), then every top-right to
+    /// bottom-left diagonal (starting from the top-left corner).
+    pub fn diagonals(&self) -> impl Iterator<Item = impl Iterator<Item = (Point, &T)> + '_> + '_ {
+        let count = (self.width + self.height).saturating_sub(1);
+        let tl_to_br = (0..count).map(move |d| {
+            let start = if d < self.height {
+                Point::new(0, self.height - 1 - d)
+            } else {
+                Point::new(d + 1 - self.height, 0)
+            };
+            self.walk(start, 1)
+        });
+        let tr_to_bl = (0..count).map(move |s| {
+            let x = s.min(self.width.saturating_sub(1));
+            self.walk(Point::new(x, s - x), -1)
+        });
+        tl_to_br.chain(tr_to_bl)
+    }
+
+    // walk down a row at a time from start, moving dx columns each step
+    fn walk(&self, start: Point, dx: isize) -> impl Iterator<Item = (Point, &T)> + '_ {
+        std::iter::successors(Some(start), move |p| {
+            Some(Point::new(p.x.checked_add_signed(dx)?, p.y + 1))
+        })
+        .map_while(move |p| self.get(p).map(|item| (p, item)))
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    // build a new grid of the given dimensions where each cell is sourced
+    // from the point in self returned by `source`
+    fn remap<F>(&self, width: usize, height: usize, source: F) -> Self
+    where
+        F: Fn(usize, usize) -> Point,
+    {
+        let mut cells = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(self[source(x, y)].clone());
+            }
+        }
+        Grid {
+            cells,
+            width,
+            height,
+        }
+    }
+
+    /// Rows become columns (mirror across the top-left to bottom-right diagonal).
+    pub fn transpose(&self) -> Self {
+        self.remap(self.height, self.width, |x, y| Point::new(y, x))
+    }
+
+    /// Rotate a quarter turn clockwise.
+    pub fn rotate_cw(&self) -> Self {
+        let h = self.height;
+        self.remap(self.height, self.width, |x, y| Point::new(y, h - 1 - x))
+    }
+
+    /// Rotate a quarter turn counter-clockwise.
+    pub fn rotate_ccw(&self) -> Self {
+        let w = self.width;
+        self.remap(self.height, self.width, |x, y| Point::new(w - 1 - y, x))
+    }
+
+    /// Reverse the order of the rows (mirror top to bottom).
+    pub fn flip_rows(&self) -> Self {
+        let h = self.height;
+        self.remap(self.width, self.height, |x, y| Point::new(x, h - 1 - y))
+    }
+}
+
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, point: Point) -> &Self::Output {
+        self.get(point)
+            .unwrap_or_else(|| panic!("{point:?} out of bounds"))
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, point: Point) -> &mut Self::Output {
+        self.get_mut(point)
+            .unwrap_or_else(|| panic!("{point:?} out of bounds"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Grid<u32> {
+        Grid::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]])
+    }
+
+    fn rows<T: Clone>(grid: &Grid<T>) -> Vec<Vec<T>> {
+        grid.rows().map(|r| r.to_vec()).collect()
+    }
+
+    #[test]
+    fn rotate_cw_four_times_is_identity() {
+        let grid = sample();
+        let rotated = grid.rotate_cw().rotate_cw().rotate_cw().rotate_cw();
+        assert_eq!(rotated, grid);
+    }
+
+    #[test]
+    fn rotations_undo_each_other() {
+        let grid = sample();
+        assert_eq!(grid.rotate_cw().rotate_ccw(), grid);
+        assert_eq!(grid.rotate_ccw().rotate_cw(), grid);
+        assert_eq!(grid.rotate_cw().rotate_cw(), grid.rotate_ccw().rotate_ccw());
+    }
+
+    #[test]
+    fn transforms() {
+        let grid = sample();
+        assert_eq!(rows(&grid.rotate_cw()), [[4, 1], [5, 2], [6, 3]]);
+        assert_eq!(rows(&grid.rotate_ccw()), [[3, 6], [2, 5], [1, 4]]);
+        assert_eq!(rows(&grid.transpose()), [[1, 4], [2, 5], [3, 6]]);
+        assert_eq!(rows(&grid.flip_rows()), [[4, 5, 6], [1, 2, 3]]);
+        assert_eq!(grid.transpose().transpose(), grid);
+        assert_eq!(grid.flip_rows().flip_rows(), grid);
+    }
+
+    #[test]
+    fn diagonals() {
+        let diagonals: Vec<Vec<u32>> = sample()
+            .diagonals()
+            .map(|d| d.map(|(_, v)| *v).collect())
+            .collect();
+        assert_eq!(
+            diagonals,
+            [
+                vec![4],
+                vec![1, 5],
+                vec![2, 6],
+                vec![3],
+                vec![1],
+                vec![2, 4],
+                vec![3, 5],
+                vec![6],
+            ]
+        );
+    }
+
+    #[test]
+    fn wrap_is_never_negative() {
+        assert_eq!(wrap(-1, 10), 9);
+        assert_eq!(wrap(-11, 10), 9);
+        assert_eq!(wrap(10, 10), 0);
+    }
+}
@@ -5,6 +5,32 @@ use std::{
     path::{Path, PathBuf},
 };
 
+pub mod cli;
+pub mod direction;
+pub mod grid;
+pub mod testing;
+
+/// Which part(s) of a day's puzzle to run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Part {
+    #[value(name = "1")]
+    One,
+    #[value(name = "2")]
+    Two,
+    #[default]
+    Both,
+}
+
+impl Part {
+    pub fn part1(&self) -> bool {
+        matches!(self, Part::One | Part::Both)
+    }
+
+    pub fn part2(&self) -> bool {
+        matches!(self, Part::Two | Part::Both)
+    }
+}
+
 #[allow(unused)]
 pub fn print_2darr<T>(data: &[Vec<T>])
 where
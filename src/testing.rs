@@ -0,0 +1,19 @@
+//! Helpers for the days' tests.
+
+/// A small xorshift PRNG, for tests that want lots of random but repeatable
+/// inputs.  The seed must be nonzero.
+pub struct XorShift(pub u64);
+
+impl XorShift {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A value in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}